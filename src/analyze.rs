@@ -0,0 +1,200 @@
+use crate::{
+    physics::{Physics, Position},
+    shape::{EColor, EShape, GenericShape},
+};
+use std::fmt::Display;
+
+/// Reason why a shape can't be produced. All but `NoCrystalSource` rule out
+/// every factory, that one only rules out the plans `Solver` makes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Impossibility {
+    /// the shape has no parts at all
+    Empty,
+    /// these parts stick together but nothing holds them up, so they would
+    /// fall down
+    Floating(Vec<Position>),
+    /// these crystals are not held up, and crystals shatter when they fall
+    FloatingCrystals(Vec<Position>),
+    /// pins come out of the pin pusher uncolored and can't be painted
+    ColoredPin(Position, EColor),
+    /// crystals always take the color of the paint they are made from
+    UnpaintedCrystal(Position),
+    /// a shape part needs a color, `u` for uncolored
+    MissingColor(Position),
+    /// an empty part can't have a color
+    ColoredEmpty(Position, EColor),
+    /// the planner has no crystal generator, so crystals can only come from
+    /// start shapes that already hold them
+    NoCrystalSource(Position),
+}
+
+fn fmt_positions(f: &mut std::fmt::Formatter<'_>, parts: &[Position]) -> std::fmt::Result {
+    for (i, (layer, part)) in parts.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "layer {} part {}", layer, part)?;
+    }
+    Ok(())
+}
+
+impl Display for Impossibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Impossibility::Empty => write!(f, "the shape is empty"),
+            Impossibility::Floating(parts) => {
+                write!(f, "nothing holds up ")?;
+                fmt_positions(f, parts)?;
+                write!(f, ", it would fall down")
+            }
+            Impossibility::FloatingCrystals(parts) => {
+                write!(f, "nothing holds up the crystals at ")?;
+                fmt_positions(f, parts)?;
+                write!(f, ", they would shatter")
+            }
+            Impossibility::ColoredPin((layer, part), color) => write!(
+                f,
+                "the pin at layer {} part {} is colored {:?}, pins can't be painted",
+                layer, part, color
+            ),
+            Impossibility::UnpaintedCrystal((layer, part)) => write!(
+                f,
+                "the crystal at layer {} part {} has no paint color",
+                layer, part
+            ),
+            Impossibility::MissingColor((layer, part)) => {
+                write!(f, "the part at layer {} part {} has no color", layer, part)
+            }
+            Impossibility::ColoredEmpty((layer, part), color) => write!(
+                f,
+                "the empty part at layer {} part {} is colored {:?}",
+                layer, part, color
+            ),
+            Impossibility::NoCrystalSource((layer, part)) => write!(
+                f,
+                "the crystal at layer {} part {} needs a crystal generator, which the planner doesn't use",
+                layer, part
+            ),
+        }
+    }
+}
+
+/// Quick checks that prove a shape can't be built, without searching.
+/// An empty list doesn't mean the shape can be built, only that none of the
+/// checks rules it out.
+pub fn analyze<const N: usize, const L: usize>(shape: &GenericShape<N, L>) -> Vec<Impossibility> {
+    let mut reasons = Vec::new();
+    if shape.layer_height() == 0 {
+        reasons.push(Impossibility::Empty);
+        return reasons;
+    }
+
+    for i in 0..L {
+        for j in 0..N {
+            let item = shape[i][j];
+            let position = (i, j);
+            match (item.shape, item.color) {
+                (EShape::Empty, EColor::Empty) => {}
+                (EShape::Empty, color) => {
+                    reasons.push(Impossibility::ColoredEmpty(position, color))
+                }
+                (EShape::Pin, EColor::Empty) => {}
+                (EShape::Pin, color) => reasons.push(Impossibility::ColoredPin(position, color)),
+                (EShape::Crystal, EColor::Empty | EColor::Uncolored) => {
+                    reasons.push(Impossibility::UnpaintedCrystal(position))
+                }
+                (_, EColor::Empty) => reasons.push(Impossibility::MissingColor(position)),
+                _ => {}
+            }
+        }
+    }
+
+    for group in shape.groups() {
+        if group.supported {
+            continue;
+        }
+        let crystals: Vec<Position> = group
+            .parts
+            .iter()
+            .copied()
+            .filter(|&(l, p)| shape[l][p].shape == EShape::Crystal)
+            .collect();
+        if crystals.is_empty() {
+            reasons.push(Impossibility::Floating(group.parts));
+        } else {
+            reasons.push(Impossibility::FloatingCrystals(crystals));
+        }
+    }
+    reasons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{shape, Shape};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_possible() {
+        for s in [
+            "CuCuCuCu",
+            "P-P-----:CuCu----",
+            "crcr----:crcr----",
+            "RrRr----:--Rr----",
+        ] {
+            assert_eq!(analyze(&shape(s)), vec![], "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_impossible() {
+        assert_eq!(analyze(&Shape::default()), vec![Impossibility::Empty]);
+        assert_eq!(
+            analyze(&shape("Cu------:--CuCu--")),
+            vec![Impossibility::Floating(vec![(1, 1), (1, 2)])]
+        );
+        assert_eq!(
+            analyze(&shape("Cu------:--cr----")),
+            vec![Impossibility::FloatingCrystals(vec![(1, 1)])]
+        );
+        assert_eq!(
+            analyze(&shape("PrCu----")),
+            vec![Impossibility::ColoredPin((0, 0), EColor::Red)]
+        );
+        assert_eq!(
+            analyze(&shape("cuC-----")),
+            vec![
+                Impossibility::UnpaintedCrystal((0, 0)),
+                Impossibility::MissingColor((0, 1))
+            ]
+        );
+        assert_eq!(
+            analyze(&shape("-rCu----")),
+            vec![Impossibility::ColoredEmpty((0, 0), EColor::Red)]
+        );
+    }
+
+    #[test]
+    fn test_reason_display() {
+        let reasons = analyze(&shape("Sb----Wm:--CcP-P-:--P-----:--Sc--Sg"));
+        let reasons: Vec<String> = reasons.iter().map(|reason| reason.to_string()).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "nothing holds up layer 1 part 1, it would fall down",
+                "nothing holds up layer 1 part 2, it would fall down",
+                "nothing holds up layer 2 part 1, it would fall down",
+                "nothing holds up layer 3 part 1, it would fall down",
+                "nothing holds up layer 3 part 3, it would fall down",
+            ]
+        );
+        assert_eq!(
+            Impossibility::ColoredPin((1, 2), EColor::Blue).to_string(),
+            "the pin at layer 1 part 2 is colored Blue, pins can't be painted"
+        );
+        assert_eq!(
+            Impossibility::NoCrystalSource((0, 3)).to_string(),
+            "the crystal at layer 0 part 3 needs a crystal generator, which the planner doesn't use"
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use shapez2_calc::{reachability::SearchCache, shape::SingleLayer};
+use std::{fs::File, io::BufReader, io::BufWriter, process::ExitCode};
+
+const USAGE: &str = "usage: reachability build <file>
+       reachability lookup <file> <layer>...
+
+The search cache holds the layers a single layer search reaches from the
+raw shapes in every paint color, each with a shortest recipe. It is not
+every layer there is: one not in the cache may still be built by
+`shapez2-calc solve`, which also swaps together halves built apart.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["build", path] => build(path),
+        ["lookup", path, ref layers @ ..] if !layers.is_empty() => lookup(path, layers),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn build(path: &str) -> Result<(), String> {
+    let cache = SearchCache::build(SearchCache::standard_start_shapes());
+    let file = File::create(path).map_err(|err| format!("{}: {}", path, err))?;
+    cache
+        .write_to(BufWriter::new(file))
+        .map_err(|err| format!("{}: {}", path, err))?;
+    println!("{} layers written to {}", cache.len(), path);
+    Ok(())
+}
+
+fn lookup(path: &str, layers: &[&str]) -> Result<(), String> {
+    let file = File::open(path).map_err(|err| format!("{}: {}", path, err))?;
+    let cache =
+        SearchCache::read_from(BufReader::new(file)).map_err(|err| format!("{}: {}", path, err))?;
+    for layer in layers {
+        let layer = SingleLayer::try_from_string(layer).map_err(|err| err.to_string())?;
+        match cache
+            .recipe(&layer)
+            .map_err(|err| format!("{}: {}", path, err))?
+        {
+            Some((start, operations)) => {
+                println!("{}: start from {}", layer, start);
+                for (i, operation) in operations.iter().enumerate() {
+                    println!("{}. {}", i + 1, operation);
+                }
+            }
+            None => println!("{}: not in cache", layer),
+        }
+    }
+    Ok(())
+}
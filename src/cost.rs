@@ -0,0 +1,182 @@
+use crate::solver::OperationKind;
+
+/// What the solver should keep small
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Objective {
+    /// every building counts as one
+    #[default]
+    FewestMachines,
+    /// sum of the per-building weights
+    Weighted,
+    /// as few kinds of buildings as possible, then as few buildings
+    FewestBuildingTypes,
+    /// sum of the building areas, falling back to the weight
+    SmallestFootprint,
+    /// sum of the building power use, falling back to the weight
+    LowestPower,
+}
+
+/// Price of every building the solver may place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostModel {
+    pub objective: Objective,
+    weights: [usize; OperationKind::ALL.len()],
+    areas: [Option<usize>; OperationKind::ALL.len()],
+    powers: [Option<usize>; OperationKind::ALL.len()],
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel::new(Objective::default())
+    }
+}
+
+impl CostModel {
+    /// Cost of the first building of a kind under `FewestBuildingTypes`, more
+    /// than any number of buildings of kinds already in use
+    pub const NEW_TYPE_COST: usize = 1 << 20;
+
+    pub fn new(objective: Objective) -> CostModel {
+        CostModel {
+            objective,
+            weights: [1; OperationKind::ALL.len()],
+            areas: [None; OperationKind::ALL.len()],
+            powers: [None; OperationKind::ALL.len()],
+        }
+    }
+
+    pub fn weight(mut self, kind: OperationKind, weight: usize) -> CostModel {
+        self.weights[kind.index()] = weight;
+        self
+    }
+
+    pub fn area(mut self, kind: OperationKind, area: usize) -> CostModel {
+        self.areas[kind.index()] = Some(area);
+        self
+    }
+
+    pub fn power(mut self, kind: OperationKind, power: usize) -> CostModel {
+        self.powers[kind.index()] = Some(power);
+        self
+    }
+
+    /// Cost of one more building of `kind`, not counting the price of a new
+    /// building type
+    pub fn cost(&self, kind: OperationKind) -> usize {
+        let i = kind.index();
+        match self.objective {
+            Objective::FewestMachines | Objective::FewestBuildingTypes => 1,
+            Objective::Weighted => self.weights[i],
+            Objective::SmallestFootprint => self.areas[i].unwrap_or(self.weights[i]),
+            Objective::LowestPower => self.powers[i].unwrap_or(self.weights[i]),
+        }
+    }
+
+    /// Cost of one more building of `kind` when the kinds in `used` are
+    /// already placed
+    pub fn step_cost(&self, kind: OperationKind, used: KindSet) -> usize {
+        match self.objective {
+            Objective::FewestBuildingTypes if !used.contains(kind) => {
+                Self::NEW_TYPE_COST + self.cost(kind)
+            }
+            _ => self.cost(kind),
+        }
+    }
+
+    /// Cheapest single building among `kinds`
+    pub fn min_cost(&self, kinds: impl IntoIterator<Item = OperationKind>) -> usize {
+        kinds
+            .into_iter()
+            .map(|kind| self.cost(kind))
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Cost of placing all of `kinds`, one building each
+    pub fn total(&self, kinds: impl IntoIterator<Item = OperationKind>) -> usize {
+        let mut used = KindSet::default();
+        let mut total = 0;
+        for kind in kinds {
+            total += self.step_cost(kind, used);
+            used.insert(kind);
+        }
+        total
+    }
+}
+
+/// Small set of building kinds, one bit per kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct KindSet(u8);
+
+impl KindSet {
+    pub fn contains(&self, kind: OperationKind) -> bool {
+        self.0 & (1 << kind.index()) != 0
+    }
+
+    pub fn insert(&mut self, kind: OperationKind) {
+        self.0 |= 1 << kind.index();
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_objectives() {
+        let kinds = [
+            OperationKind::Cut,
+            OperationKind::Rotate,
+            OperationKind::Cut,
+        ];
+        let model = CostModel::new(Objective::FewestMachines)
+            .weight(OperationKind::Cut, 5)
+            .area(OperationKind::Rotate, 3);
+        assert_eq!(model.total(kinds), 3);
+        assert_eq!(
+            CostModel {
+                objective: Objective::Weighted,
+                ..model
+            }
+            .total(kinds),
+            11
+        );
+        assert_eq!(
+            CostModel {
+                objective: Objective::SmallestFootprint,
+                ..model
+            }
+            .total(kinds),
+            13
+        );
+        assert_eq!(
+            CostModel {
+                objective: Objective::FewestBuildingTypes,
+                ..model
+            }
+            .total(kinds),
+            2 * CostModel::NEW_TYPE_COST + 3
+        );
+    }
+
+    #[test]
+    fn test_kind_set() {
+        let mut set = KindSet::default();
+        assert!(set.is_empty());
+        set.insert(OperationKind::Stack);
+        set.insert(OperationKind::Stack);
+        set.insert(OperationKind::Rotate);
+        assert!(set.contains(OperationKind::Stack));
+        assert!(!set.contains(OperationKind::Cut));
+        assert_eq!(set.len(), 2);
+    }
+}
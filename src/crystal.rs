@@ -0,0 +1,68 @@
+use crate::shape::{EColor, EShape, GenericLayer, GenericShape, SingleItem};
+
+/// The crystal generator fills every empty or pin slot of a shape, up to its
+/// current height, with crystals of the given color
+pub trait Crystallizable: Sized + Copy {
+    fn crystallized(&mut self, color: EColor);
+    fn crystallize(&self, color: EColor) -> Self {
+        let mut shape = *self;
+        shape.crystallized(color);
+        shape
+    }
+}
+
+impl<const N: usize> Crystallizable for GenericLayer<N> {
+    fn crystallized(&mut self, color: EColor) {
+        if !self.is_some() {
+            return;
+        }
+        for j in 0..N {
+            if matches!(self.items[j].shape, EShape::Empty | EShape::Pin) {
+                self.items[j] = SingleItem {
+                    shape: EShape::Crystal,
+                    color,
+                };
+            }
+        }
+    }
+}
+
+impl<const N: usize, const L: usize> Crystallizable for GenericShape<N, L> {
+    fn crystallized(&mut self, color: EColor) {
+        for i in 0..self.layer_height() {
+            for j in 0..N {
+                if matches!(self[i][j].shape, EShape::Empty | EShape::Pin) {
+                    self[i][j] = SingleItem {
+                        shape: EShape::Crystal,
+                        color,
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{shape, Shape, SingleLayer};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_crystallize() {
+        assert_eq!(
+            shape("P-Cu----:--Cu----").crystallize(EColor::Red),
+            shape("crCucrcr:crCucrcr")
+        );
+        assert_eq!(Shape::default().crystallize(EColor::Red), Shape::default());
+    }
+
+    #[test]
+    fn test_crystallize_layer() {
+        let layer = SingleLayer::try_from_string("Cu--P---").unwrap();
+        assert_eq!(
+            layer.crystallize(EColor::Blue),
+            SingleLayer::try_from_string("Cucbcbcb").unwrap()
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
+    physics::{self, Physics},
     rotate::Rotatable,
-    shape::{Shape, SingleItem, SingleLayer, SHAPEZ2_DEMENTION, SHAPEZ2_LAYER},
+    shape::{GenericLayer, GenericShape, SingleItem},
 };
 
 pub trait Cuttable: Sized + Copy + Rotatable {
@@ -32,34 +33,45 @@ pub trait Cuttable: Sized + Copy + Rotatable {
     }
 }
 
-impl Cuttable for Shape {
+/// Crystals stuck across the cut line shatter before cutting, and parts left
+/// unsupported by a cut or swap fall down afterwards.
+impl<const N: usize, const L: usize> Cuttable for GenericShape<N, L> {
     fn half_destroyed(&mut self) {
-        for i in 0..SHAPEZ2_LAYER {
+        physics::shatter_cut_crystals(&mut self.items);
+        for i in 0..L {
             self[i].half_destroyed();
         }
+        self.collapsed();
     }
 
     fn swapd(a: &mut Self, b: &mut Self) {
-        for i in 0..SHAPEZ2_LAYER {
-            SingleLayer::swapd(&mut a[i], &mut b[i]);
+        physics::shatter_cut_crystals(&mut a.items);
+        physics::shatter_cut_crystals(&mut b.items);
+        for i in 0..L {
+            GenericLayer::swapd(&mut a[i], &mut b[i]);
         }
+        a.collapsed();
+        b.collapsed();
     }
 }
 
-impl Cuttable for SingleLayer {
+impl<const N: usize> Cuttable for GenericLayer<N> {
     fn half_destroyed(&mut self) {
-        for j in 0..SHAPEZ2_DEMENTION {
-            if j * 2 / SHAPEZ2_DEMENTION > 0 {
+        physics::shatter_cut_crystals(std::slice::from_mut(self));
+        for j in 0..N {
+            if j * 2 / N > 0 {
                 self.items[j] = SingleItem::default();
             }
         }
     }
 
     fn swapd(a: &mut Self, b: &mut Self) {
+        physics::shatter_cut_crystals(std::slice::from_mut(a));
+        physics::shatter_cut_crystals(std::slice::from_mut(b));
         let ori_a = *a;
         let ori_b = *b;
-        for j in 0..SHAPEZ2_DEMENTION {
-            if j * 2 / SHAPEZ2_DEMENTION > 0 {
+        for j in 0..N {
+            if j * 2 / N > 0 {
                 a.items[j] = ori_b.items[j];
                 b.items[j] = ori_a.items[j];
             }
@@ -70,11 +82,31 @@ impl Cuttable for SingleLayer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shape::{
+        shape, EColor, EShape, HexShape, Shape, SingleLayer, SHAPEZ2_DEMENTION,
+        SHAPEZ2_HEX_DEMENTION, SHAPEZ2_LAYER,
+    };
     use pretty_assertions::assert_eq;
 
+    /// Random shape without holes, so nothing falls when it is cut
+    fn random_solid_shape() -> Shape {
+        let mut shape = Shape::random();
+        for i in 0..shape.layer_height() {
+            for item in shape[i].items.iter_mut() {
+                if item.shape == EShape::Empty {
+                    *item = SingleItem {
+                        shape: EShape::Circle,
+                        color: EColor::Uncolored,
+                    };
+                }
+            }
+        }
+        shape
+    }
+
     #[test]
     fn test_half_destroy() {
-        let shape = Shape::random();
+        let shape = random_solid_shape();
         let new_shape = shape.half_destroy();
         for i in 0..SHAPEZ2_LAYER {
             for j in 0..SHAPEZ2_DEMENTION {
@@ -89,7 +121,7 @@ mod tests {
 
     #[test]
     fn test_cutting() {
-        let shape = Shape::random();
+        let shape = random_solid_shape();
         let [left_shape, right_shape] = shape.cutting();
         for i in 0..SHAPEZ2_LAYER {
             for j in 0..SHAPEZ2_DEMENTION {
@@ -106,8 +138,8 @@ mod tests {
 
     #[test]
     fn test_swap() {
-        let shape_a = Shape::random();
-        let shape_b = Shape::random();
+        let shape_a = random_solid_shape();
+        let shape_b = random_solid_shape();
         let [new_shape_a, new_shape_b] = Shape::swap(&shape_a, &shape_b);
         for i in 0..SHAPEZ2_LAYER {
             for j in 0..SHAPEZ2_DEMENTION {
@@ -124,12 +156,63 @@ mod tests {
 
     #[test]
     fn test_swap_with() {
-        let mut shape_a = Shape::random();
-        let mut shape_b = Shape::random();
+        let mut shape_a = random_solid_shape();
+        let mut shape_b = random_solid_shape();
         let [new_shape_a, new_shape_b] = shape_a.swap_with(&shape_b);
         shape_a.swapd_with(&mut shape_b);
 
         assert_eq!(new_shape_a, shape_a);
         assert_eq!(new_shape_b, shape_b);
     }
+
+    #[test]
+    fn test_cutting_collapses() {
+        let [left_shape, right_shape] = shape("Cu------:CuCuCuCu").cutting();
+        assert_eq!(left_shape, shape("----CuCu"));
+        assert_eq!(right_shape, shape("Cu------:CuCu----"));
+
+        let pinned = shape("P-P-P-P-:CuCuCuCu");
+        assert_eq!(pinned.half_destroy(), shape("P-P-----:CuCu----"));
+    }
+
+    #[test]
+    fn test_swap_collapses() {
+        let [new_shape_a, new_shape_b] =
+            Shape::swap(&shape("CuCuCuCu"), &shape("--Ru----:RuRuRuRu"));
+        assert_eq!(new_shape_a, shape("CuCuRuRu"));
+        assert_eq!(new_shape_b, shape("--RuCuCu:RuRu----"));
+    }
+
+    #[test]
+    fn test_cutting_shatters_crystals() {
+        let [left_shape, right_shape] = shape("CucrcrCu:crcr----").cutting();
+        assert_eq!(left_shape, shape("------Cu"));
+        assert_eq!(right_shape, shape("Cu------"));
+
+        let [left_shape, right_shape] = shape("CucrRuCu:crcr----").cutting();
+        assert_eq!(left_shape, shape("----RuCu"));
+        assert_eq!(right_shape, shape("Cucr----:crcr----"));
+
+        let [left_layer, right_layer] = SingleLayer::try_from_string("crcrcrcr").unwrap().cutting();
+        assert_eq!(left_layer, SingleLayer::default());
+        assert_eq!(right_layer, SingleLayer::default());
+    }
+
+    #[test]
+    fn test_cutting_hex() {
+        let hex = HexShape::try_from_string("CuRuSuWuCrRr:CuCuCuCuCuCu").unwrap();
+        let [left_shape, right_shape] = hex.cutting();
+        for j in 0..SHAPEZ2_HEX_DEMENTION {
+            if j < SHAPEZ2_HEX_DEMENTION / 2 {
+                assert_eq!(left_shape.items[0][j], SingleItem::new());
+                assert_eq!(right_shape.items[0][j], hex.items[0][j]);
+            } else {
+                assert_eq!(left_shape.items[0][j], hex.items[0][j]);
+                assert_eq!(right_shape.items[0][j], SingleItem::new());
+            }
+        }
+        let [new_left, new_right] = HexShape::swap(&left_shape, &right_shape);
+        assert_eq!(new_left, HexShape::default());
+        assert_eq!(new_right, hex);
+    }
 }
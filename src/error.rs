@@ -0,0 +1,192 @@
+use std::fmt::Display;
+
+/// Why a shape identifier could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// not one of the shape codes `C`, `R`, `W`, `S`, `P`, `c` or `-`
+    InvalidShape(String),
+    /// not one of the color codes `r`, `g`, `b`, `y`, `m`, `c`, `w`, `k`, `u` or `-`
+    InvalidColor(String),
+    /// a part or a layer doesn't have the expected number of characters
+    WrongLength { expected: usize, found: usize },
+    /// the identifier has more `:`-separated layers than the shape allows
+    TooManyLayers { max: usize, found: usize },
+}
+
+/// Error returned when parsing a shape identifier, pointing at the layer,
+/// part and character offset (counted in chars from the start of the
+/// input) where parsing failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShapeParseError {
+    pub layer: Option<usize>,
+    pub part: Option<usize>,
+    pub offset: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl ShapeParseError {
+    pub fn new(kind: ParseErrorKind) -> ShapeParseError {
+        ShapeParseError {
+            layer: None,
+            part: None,
+            offset: 0,
+            kind,
+        }
+    }
+
+    /// Shift the error to `offset` characters further in the input
+    pub fn at_offset(mut self, offset: usize) -> ShapeParseError {
+        self.offset += offset;
+        self
+    }
+
+    /// Locate the error in part `part`, starting `offset` characters further
+    pub fn in_part(mut self, part: usize, offset: usize) -> ShapeParseError {
+        self.part = Some(part);
+        self.at_offset(offset)
+    }
+
+    /// Locate the error in layer `layer`, starting `offset` characters further
+    pub fn in_layer(mut self, layer: usize, offset: usize) -> ShapeParseError {
+        self.layer = Some(layer);
+        self.at_offset(offset)
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::InvalidShape(code) => write!(f, "invalid shape code {:?}", code),
+            ParseErrorKind::InvalidColor(code) => write!(f, "invalid color code {:?}", code),
+            ParseErrorKind::WrongLength { expected, found } => {
+                write!(f, "expected {} characters, found {}", expected, found)
+            }
+            ParseErrorKind::TooManyLayers { max, found } => {
+                write!(f, "expected at most {} layers, found {}", max, found)
+            }
+        }
+    }
+}
+
+impl Display for ShapeParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(layer) = self.layer {
+            write!(f, " in layer {}", layer)?;
+        }
+        if let Some(part) = self.part {
+            write!(f, " at part {}", part)?;
+        }
+        write!(f, " (offset {})", self.offset)
+    }
+}
+
+impl std::error::Error for ShapeParseError {}
+
+/// Why a shape expression could not be parsed or evaluated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalErrorKind {
+    /// a character that can't start any token
+    UnexpectedChar(char),
+    /// a token other than the one the grammar needs here, `None` at the end
+    /// of the input
+    Expected {
+        expected: &'static str,
+        found: Option<String>,
+    },
+    /// a name that is neither a variable nor a shape identifier
+    UnknownName {
+        name: String,
+        error: ShapeParseError,
+    },
+    /// a variable name that would also read as a shape identifier
+    InvalidName(String),
+    UnknownFunction(String),
+    WrongArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    /// a function argument that must be a color code
+    InvalidColor(String),
+    /// a value with several outputs used where a single shape is needed
+    NotSingle {
+        outputs: usize,
+    },
+    IndexOutOfRange {
+        index: usize,
+        outputs: usize,
+    },
+    /// an assignment to a different number of names than the value has
+    /// outputs
+    WrongOutputCount {
+        names: usize,
+        outputs: usize,
+    },
+}
+
+impl Display for EvalErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+            EvalErrorKind::Expected {
+                expected,
+                found: Some(found),
+            } => write!(f, "expected {}, found {:?}", expected, found),
+            EvalErrorKind::Expected {
+                expected,
+                found: None,
+            } => write!(f, "expected {}, found the end of the input", expected),
+            EvalErrorKind::UnknownName { name, error } => write!(
+                f,
+                "{:?} is neither a variable nor a shape: {}",
+                name, error.kind
+            ),
+            EvalErrorKind::InvalidName(name) => {
+                write!(f, "{:?} is a shape identifier, not a variable name", name)
+            }
+            EvalErrorKind::UnknownFunction(name) => write!(f, "unknown function {:?}", name),
+            EvalErrorKind::WrongArgumentCount {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} arguments, found {}",
+                function, expected, found
+            ),
+            EvalErrorKind::InvalidColor(code) => write!(f, "invalid color code {:?}", code),
+            EvalErrorKind::NotSingle { outputs } => {
+                write!(f, "expected a single shape, found {} outputs", outputs)
+            }
+            EvalErrorKind::IndexOutOfRange { index, outputs } => {
+                write!(f, "index {} out of range for {} outputs", index, outputs)
+            }
+            EvalErrorKind::WrongOutputCount { names, outputs } => {
+                write!(f, "can't assign {} outputs to {} names", outputs, names)
+            }
+        }
+    }
+}
+
+/// Error returned by the expression evaluator, with the character offset
+/// of the token or expression it is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalError {
+    pub offset: usize,
+    pub kind: EvalErrorKind,
+}
+
+impl EvalError {
+    pub fn new(kind: EvalErrorKind, offset: usize) -> EvalError {
+        EvalError { offset, kind }
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (offset {})", self.kind, self.offset)
+    }
+}
+
+impl std::error::Error for EvalError {}
@@ -0,0 +1,672 @@
+//! A small language for chaining shape operations.
+//!
+//! ```plaintext
+//! x = rot_cw(Cu------)
+//! west, east = cut(CuCuRuRu)
+//! stack(x, swap(west, RrRrRrRr)[1])
+//! ```
+//!
+//! Statements are separated by `;` or new lines. A statement is either an
+//! expression or an assignment of one to variables. An expression is a shape
+//! identifier, a variable, a function call or an expression followed by
+//! `[i]` to pick one of its outputs. `cut` and `swap` have two outputs, which
+//! can be assigned to two names at once.
+//!
+//! Functions: `rot_cw`, `rot_ccw`, `rot_180`, `cut`, `cut_left`,
+//! `cut_right`, `half_destroy`, `swap`, `stack`, `pin_push`, `collapse`,
+//! `paint` and `crystallize`, the last two taking a shape and a color code.
+
+use crate::{
+    crystal::Crystallizable,
+    cutting::Cuttable,
+    error::{EvalError, EvalErrorKind},
+    paint::Paintable,
+    physics::Physics,
+    pin::PinPushable,
+    rotate::{Rotatable, RotateDirection},
+    shape::{EColor, Shape},
+    stack::Stackable,
+};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// a shape identifier, variable, function name, color code or index
+    Word(String),
+    Open,
+    Close,
+    OpenIndex,
+    CloseIndex,
+    Comma,
+    Assign,
+    /// end of a statement, `;` or a new line
+    Separator,
+}
+
+impl Token {
+    fn text(&self) -> String {
+        match self {
+            Token::Word(word) => word.clone(),
+            Token::Open => "(".to_string(),
+            Token::Close => ")".to_string(),
+            Token::OpenIndex => "[".to_string(),
+            Token::CloseIndex => "]".to_string(),
+            Token::Comma => ",".to_string(),
+            Token::Assign => "=".to_string(),
+            Token::Separator => ";".to_string(),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | ':')
+}
+
+/// Tokens with their offsets in chars
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, EvalError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let token = match chars[i] {
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '[' => Token::OpenIndex,
+            ']' => Token::CloseIndex,
+            ',' => Token::Comma,
+            '=' => Token::Assign,
+            ';' | '\n' => Token::Separator,
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            c if is_word_char(c) => {
+                let start = i;
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                tokens.push((Token::Word(chars[start..i].iter().collect()), start));
+                continue;
+            }
+            c => return Err(EvalError::new(EvalErrorKind::UnexpectedChar(c), i)),
+        };
+        tokens.push((token, i));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    /// a variable or a shape identifier, or a color code as an argument
+    Name(String, usize),
+    Call {
+        function: String,
+        args: Vec<Expr>,
+        offset: usize,
+    },
+    Index {
+        value: Box<Expr>,
+        index: usize,
+        offset: usize,
+    },
+}
+
+impl Expr {
+    /// The word the expression starts with, found at its offset
+    fn first_word(&self) -> &str {
+        match self {
+            Expr::Name(word, _) | Expr::Call { function: word, .. } => word,
+            Expr::Index { value, .. } => value.first_word(),
+        }
+    }
+
+    fn offset(&self) -> usize {
+        match self {
+            Expr::Name(_, offset) | Expr::Call { offset, .. } | Expr::Index { offset, .. } => {
+                *offset
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Statement {
+    /// names the value is assigned to, with their offsets
+    names: Vec<(String, usize)>,
+    value: Expr,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// offset reported for errors at the end of the input
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |&(_, offset)| offset)
+    }
+
+    fn error(&self, expected: &'static str) -> EvalError {
+        EvalError::new(
+            EvalErrorKind::Expected {
+                expected,
+                found: self.peek().map(Token::text),
+            },
+            self.offset(),
+        )
+    }
+
+    fn expect(&mut self, token: Token, expected: &'static str) -> Result<(), EvalError> {
+        if self.peek() != Some(&token) {
+            return Err(self.error(expected));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn word(&mut self, expected: &'static str) -> Result<(String, usize), EvalError> {
+        match self.tokens.get(self.position) {
+            Some((Token::Word(word), offset)) => {
+                self.position += 1;
+                Ok((word.clone(), *offset))
+            }
+            _ => Err(self.error(expected)),
+        }
+    }
+
+    fn program(&mut self) -> Result<Vec<Statement>, EvalError> {
+        let mut statements = Vec::new();
+        loop {
+            while self.peek() == Some(&Token::Separator) {
+                self.position += 1;
+            }
+            if self.peek().is_none() {
+                return Ok(statements);
+            }
+            statements.push(self.statement()?);
+            if self.peek().is_some() {
+                self.expect(Token::Separator, "`;` or a new line")?;
+            }
+        }
+    }
+
+    /// Whether the tokens ahead are names separated by commas, then `=`
+    fn at_assignment(&self) -> bool {
+        let mut i = self.position;
+        loop {
+            if !matches!(self.tokens.get(i), Some((Token::Word(_), _))) {
+                return false;
+            }
+            match self.tokens.get(i + 1) {
+                Some((Token::Comma, _)) => i += 2,
+                Some((Token::Assign, _)) => return true,
+                _ => return false,
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, EvalError> {
+        let mut names = Vec::new();
+        if self.at_assignment() {
+            loop {
+                names.push(self.word("a variable name")?);
+                if self.peek() == Some(&Token::Assign) {
+                    self.position += 1;
+                    break;
+                }
+                self.expect(Token::Comma, "`,` or `=`")?;
+            }
+        }
+        Ok(Statement {
+            names,
+            value: self.expr()?,
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr, EvalError> {
+        let (word, offset) = self.word("a shape, variable or function call")?;
+        let mut expr = if self.peek() == Some(&Token::Open) {
+            self.position += 1;
+            let mut args = Vec::new();
+            if self.peek() != Some(&Token::Close) {
+                loop {
+                    args.push(self.expr()?);
+                    if self.peek() != Some(&Token::Comma) {
+                        break;
+                    }
+                    self.position += 1;
+                }
+            }
+            self.expect(Token::Close, "`,` or `)`")?;
+            Expr::Call {
+                function: word,
+                args,
+                offset,
+            }
+        } else {
+            Expr::Name(word, offset)
+        };
+
+        while self.peek() == Some(&Token::OpenIndex) {
+            self.position += 1;
+            let index_offset = self.offset();
+            let (index, _) = self.word("an index")?;
+            let index = index.parse().map_err(|_| {
+                EvalError::new(
+                    EvalErrorKind::Expected {
+                        expected: "an index",
+                        found: Some(index),
+                    },
+                    index_offset,
+                )
+            })?;
+            self.expect(Token::CloseIndex, "`]`")?;
+            expr = Expr::Index {
+                value: Box::new(expr),
+                index,
+                offset,
+            };
+        }
+        Ok(expr)
+    }
+}
+
+fn parse(source: &str) -> Result<Vec<Statement>, EvalError> {
+    Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+        end: source.chars().count(),
+    }
+    .program()
+}
+
+/// Whether `name` can be used as a variable: letters, digits and `_`, not
+/// starting with a digit, and not readable as a shape identifier such as
+/// `Cu`
+pub fn is_variable_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && Shape::try_from_string(name).is_err()
+}
+
+/// Evaluates shape expressions and keeps the variables they assign.
+///
+/// Every value is a list of shapes, holding a single shape unless it comes
+/// from an operation with several outputs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Evaluator {
+    variables: BTreeMap<String, Vec<Shape>>,
+}
+
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator::default()
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&[Shape]> {
+        self.variables.get(name).map(Vec::as_slice)
+    }
+
+    /// All variables, sorted by name
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &[Shape])> {
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_slice()))
+    }
+
+    pub fn set_variable(&mut self, name: &str, value: Vec<Shape>) -> Result<(), EvalError> {
+        if !is_variable_name(name) {
+            return Err(EvalError::new(
+                EvalErrorKind::InvalidName(name.to_string()),
+                0,
+            ));
+        }
+        self.variables.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    pub fn remove_variable(&mut self, name: &str) -> Option<Vec<Shape>> {
+        self.variables.remove(name)
+    }
+
+    /// Run every statement of `source` and return the value of the last
+    /// one, empty if there is none or if it is an assignment. Statements
+    /// before a failing one keep their effect on the variables.
+    pub fn eval(&mut self, source: &str) -> Result<Vec<Shape>, EvalError> {
+        let mut result = Vec::new();
+        for statement in parse(source)? {
+            result = self.value(&statement.value)?;
+            match statement.names[..] {
+                [] => continue,
+                [(ref name, offset)] => {
+                    // a single name takes the whole value, outputs and all
+                    self.assign(name, offset, result.clone())?;
+                }
+                ref names => {
+                    if names.len() != result.len() {
+                        return Err(EvalError::new(
+                            EvalErrorKind::WrongOutputCount {
+                                names: names.len(),
+                                outputs: result.len(),
+                            },
+                            names[0].1,
+                        ));
+                    }
+                    for ((name, offset), shape) in names.iter().zip(result.iter()) {
+                        self.assign(name, *offset, vec![*shape])?;
+                    }
+                }
+            }
+            result.clear();
+        }
+        Ok(result)
+    }
+
+    fn assign(&mut self, name: &str, offset: usize, value: Vec<Shape>) -> Result<(), EvalError> {
+        self.set_variable(name, value)
+            .map_err(|err| EvalError { offset, ..err })
+    }
+
+    fn value(&self, expr: &Expr) -> Result<Vec<Shape>, EvalError> {
+        match expr {
+            Expr::Name(name, offset) => match self.variables.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Shape::try_from_string(name)
+                    .map(|shape| vec![shape])
+                    .map_err(|error| {
+                        EvalError::new(
+                            EvalErrorKind::UnknownName {
+                                name: name.clone(),
+                                error,
+                            },
+                            *offset,
+                        )
+                    }),
+            },
+            Expr::Call {
+                function,
+                args,
+                offset,
+            } => self.call(function, args, *offset),
+            Expr::Index { value, index, .. } => {
+                let outputs = self.value(value)?;
+                match outputs.get(*index) {
+                    Some(shape) => Ok(vec![*shape]),
+                    None => Err(EvalError::new(
+                        EvalErrorKind::IndexOutOfRange {
+                            index: *index,
+                            outputs: outputs.len(),
+                        },
+                        expr.offset(),
+                    )),
+                }
+            }
+        }
+    }
+
+    fn single(&self, expr: &Expr) -> Result<Shape, EvalError> {
+        match self.value(expr)?[..] {
+            [shape] => Ok(shape),
+            ref outputs => Err(EvalError::new(
+                EvalErrorKind::NotSingle {
+                    outputs: outputs.len(),
+                },
+                expr.offset(),
+            )),
+        }
+    }
+
+    /// A color code argument, written as a bare name like `r`
+    fn color(expr: &Expr) -> Result<EColor, EvalError> {
+        let Expr::Name(code, offset) = expr else {
+            return Err(EvalError::new(
+                EvalErrorKind::Expected {
+                    expected: "a color code",
+                    found: Some(expr.first_word().to_string()),
+                },
+                expr.offset(),
+            ));
+        };
+        EColor::try_from_string(code)
+            .map_err(|_| EvalError::new(EvalErrorKind::InvalidColor(code.clone()), *offset))
+    }
+
+    /// The `K` arguments of a call, each a single shape
+    fn shapes<const K: usize>(
+        &self,
+        function: &str,
+        args: &[Expr],
+        offset: usize,
+    ) -> Result<[Shape; K], EvalError> {
+        if args.len() != K {
+            return Err(EvalError::new(
+                EvalErrorKind::WrongArgumentCount {
+                    function: function.to_string(),
+                    expected: K,
+                    found: args.len(),
+                },
+                offset,
+            ));
+        }
+        let mut shapes = [Shape::default(); K];
+        for (shape, arg) in shapes.iter_mut().zip(args) {
+            *shape = self.single(arg)?;
+        }
+        Ok(shapes)
+    }
+
+    /// A shape and a color, for the painter and the crystal generator
+    fn shape_and_color(
+        &self,
+        function: &str,
+        args: &[Expr],
+        offset: usize,
+    ) -> Result<(Shape, EColor), EvalError> {
+        match args {
+            [shape, color] => Ok((self.single(shape)?, Self::color(color)?)),
+            _ => Err(EvalError::new(
+                EvalErrorKind::WrongArgumentCount {
+                    function: function.to_string(),
+                    expected: 2,
+                    found: args.len(),
+                },
+                offset,
+            )),
+        }
+    }
+
+    fn call(&self, function: &str, args: &[Expr], offset: usize) -> Result<Vec<Shape>, EvalError> {
+        let value = match function {
+            "rot_cw" => {
+                let [x] = self.shapes(function, args, offset)?;
+                vec![x.rotate(RotateDirection::Clockwise, 1)]
+            }
+            "rot_ccw" => {
+                let [x] = self.shapes(function, args, offset)?;
+                vec![x.rotate(RotateDirection::CounterClockwise, 1)]
+            }
+            "rot_180" => {
+                let [x] = self.shapes(function, args, offset)?;
+                vec![x.rotate_180()]
+            }
+            "cut" => {
+                let [x] = self.shapes(function, args, offset)?;
+                x.cutting().to_vec()
+            }
+            "cut_left" => {
+                let [x] = self.shapes(function, args, offset)?;
+                vec![x.cutting()[0]]
+            }
+            "cut_right" => {
+                let [x] = self.shapes(function, args, offset)?;
+                vec![x.cutting()[1]]
+            }
+            "half_destroy" => {
+                let [x] = self.shapes(function, args, offset)?;
+                vec![x.half_destroy()]
+            }
+            "swap" => {
+                let [a, b] = self.shapes(function, args, offset)?;
+                Shape::swap(&a, &b).to_vec()
+            }
+            "stack" => {
+                let [bottom, top] = self.shapes(function, args, offset)?;
+                vec![Shape::stack(&bottom, &top)]
+            }
+            "pin_push" => {
+                let [x] = self.shapes(function, args, offset)?;
+                vec![x.pin_push()]
+            }
+            "collapse" => {
+                let [x] = self.shapes(function, args, offset)?;
+                vec![x.collapse()]
+            }
+            "paint" => {
+                let (x, color) = self.shape_and_color(function, args, offset)?;
+                vec![x.paint_top(color)]
+            }
+            "crystallize" => {
+                let (x, color) = self.shape_and_color(function, args, offset)?;
+                vec![x.crystallize(color)]
+            }
+            _ => {
+                return Err(EvalError::new(
+                    EvalErrorKind::UnknownFunction(function.to_string()),
+                    offset,
+                ))
+            }
+        };
+        Ok(value)
+    }
+}
+
+/// Evaluate `source` without any variables set beforehand
+pub fn eval(source: &str) -> Result<Vec<Shape>, EvalError> {
+    Evaluator::new().eval(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::shape;
+    use pretty_assertions::assert_eq;
+
+    fn shapes(s: &[&str]) -> Vec<Shape> {
+        s.iter().map(|s| shape(s)).collect()
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(
+            eval("stack(rot_cw(Cu------), cut_left(RrRrRrRr))").unwrap(),
+            shapes(&["--CuRrRr"])
+        );
+        assert_eq!(
+            eval("cut(CuCuRuRu)").unwrap(),
+            shapes(&["----RuRu", "CuCu----"])
+        );
+        assert_eq!(
+            eval("swap(CuCuCuCu, RrRrRrRr)[1]").unwrap(),
+            shapes(&["RrRrCuCu"])
+        );
+        assert_eq!(eval("paint(CuCuCuCu, r)").unwrap(), shapes(&["CrCrCrCr"]));
+        assert_eq!(
+            eval("crystallize(pin_push(Cu------), g)").unwrap(),
+            shapes(&["cgcgcgcg:Cucgcgcg"])
+        );
+        assert_eq!(eval("").unwrap(), vec![]);
+        assert_eq!(eval("x = Cu").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut evaluator = Evaluator::new();
+        let result = evaluator
+            .eval("x = rot_180(CuRu----)\nwest, east = cut(x); halves = cut(x)\nstack(east, west)")
+            .unwrap();
+        assert_eq!(result, shapes(&["----CuRu"]));
+        assert_eq!(evaluator.variable("x"), Some(&shapes(&["----CuRu"])[..]));
+        assert_eq!(evaluator.variable("halves").unwrap().len(), 2);
+        assert_eq!(evaluator.eval("halves[0]").unwrap(), shapes(&["----CuRu"]));
+        let names: Vec<&str> = evaluator.variables().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["east", "halves", "west", "x"]);
+
+        assert!(evaluator.set_variable("Cu", shapes(&["Cu"])).is_err());
+        assert!(!is_variable_name("cr"));
+        assert!(is_variable_name("base_2"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| eval(source).unwrap_err();
+        assert_eq!(
+            error("rot_cw(Cu, Cu)"),
+            EvalError::new(
+                EvalErrorKind::WrongArgumentCount {
+                    function: "rot_cw".to_string(),
+                    expected: 1,
+                    found: 2
+                },
+                0
+            )
+        );
+        assert_eq!(
+            error("stack(Cu, cut(Cu))").kind,
+            EvalErrorKind::NotSingle { outputs: 2 }
+        );
+        assert_eq!(error("stack(Cu, cut(Cu))").offset, 10);
+        assert_eq!(
+            error("cut(Cu)[2]").kind,
+            EvalErrorKind::IndexOutOfRange {
+                index: 2,
+                outputs: 2
+            }
+        );
+        assert_eq!(
+            error("a, b, c = cut(Cu)").kind,
+            EvalErrorKind::WrongOutputCount {
+                names: 3,
+                outputs: 2
+            }
+        );
+        assert_eq!(
+            error("foo(Cu)").kind,
+            EvalErrorKind::UnknownFunction("foo".into())
+        );
+        assert_eq!(
+            error("paint(Cu, cut(Cu)[0])").to_string(),
+            "expected a color code, found \"cut\" (offset 10)"
+        );
+        assert_eq!(
+            error("paint(Cu, q)").kind,
+            EvalErrorKind::InvalidColor("q".into())
+        );
+        assert_eq!(
+            error("Cu = Ru").kind,
+            EvalErrorKind::InvalidName("Cu".into())
+        );
+        assert_eq!(
+            error("Cu ! Ru"),
+            EvalError::new(EvalErrorKind::UnexpectedChar('!'), 3)
+        );
+        assert_eq!(
+            error("stack(Cu, Ru").to_string(),
+            "expected `,` or `)`, found the end of the input (offset 12)"
+        );
+        assert_eq!(
+            error("rot_cw(xy)").to_string(),
+            "\"xy\" is neither a variable nor a shape: invalid shape code \"x\" (offset 7)"
+        );
+    }
+}
@@ -1,4 +1,18 @@
+pub mod analyze;
+pub mod cost;
+pub mod crystal;
 pub mod cutting;
+pub mod error;
+pub mod expr;
+pub mod packed;
+pub mod paint;
+pub mod physics;
+pub mod pin;
+pub mod reachability;
+pub mod render;
+pub mod repl;
+pub mod reverse;
 pub mod rotate;
 pub mod shape;
+pub mod solver;
 pub mod stack;
@@ -1,168 +1,373 @@
 use shapez2_calc::{
+    analyze::analyze,
+    cost::{CostModel, Objective},
     cutting::Cuttable,
-    rotate::Rotatable,
-    shape::{EColor, EShape, Shape, SingleItem, SingleLayer, SHAPEZ2_DEMENTION, SHAPEZ2_LAYER},
+    expr::Evaluator,
+    physics::Physics,
+    reachability::SearchCache,
+    render::{render_svg, render_terminal},
+    repl::{Reply, Workspace},
+    rotate::{Rotatable, RotateDirection},
+    shape::{Shape, SingleLayer, SHAPEZ2_LAYER},
+    solver::Solver,
     stack::Stackable,
 };
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
-use std::collections::HashSet;
-
-pub fn needed_layers(items: Vec<SingleItem>) -> Vec<SingleLayer> {
-    let mut layers = Vec::new();
-    items.iter().for_each(|item| {
-        let layer = SingleLayer::new_with_shape_color(item.shape, item.color);
-        layers.push(layer);
-    });
-    layers
-}
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    process::ExitCode,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
-#[derive(Clone, Eq, PartialEq)]
-struct State {
-    layer: SingleLayer,
-    path: Vec<SingleLayer>,
-    cost: usize,
-    heuristic: usize,
-}
+const USAGE: &str = "usage: shapez2-calc <command> [shape...]
+
+commands:
+  solve [option...] <shape>...
+                              print a recipe for each shape
+  rotate <cw|ccw|180> <shape>...
+                              rotate each shape
+  cut <shape>...              cut each shape into its west and east halves
+  swap <shape> <shape>        swap the west halves of two shapes
+  stack <bottom> <top>...     stack the shapes from the bottom up
+  viewer-url <shape>...       print a link to the shape viewer for each shape
+  render <shape>...           draw the layers of each shape, in color on a
+                              terminal
+  svg <shape>...              print an SVG image of each shape, one per line
+  random [count]              print random shapes that can be built
+  validate <shape>...         check each shape for reasons it can't be built
+  normalize <shape>...        print each shape in its shortest form
+  eval <expression>           evaluate a shape expression, such as
+                              stack(rot_cw(Cu------), cut_left(RrRrRrRr))
+  repl                        work on named shapes interactively
 
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> Ordering {
-        (other.cost + other.heuristic).cmp(&(self.cost + self.heuristic))
+solve options:
+  --max-nodes <count>         give up after expanding this many states
+  --time-limit <seconds>      give up after this much time
+  --objective <name>          what to keep small: fewest-machines (the
+                              default), weighted, fewest-building-types,
+                              smallest-footprint or lowest-power
+  --bidirectional             search layers from both ends at once
+  --threads <count>           search the goal's layers and their halves
+                              ahead of the planner on this many threads,
+                              each layer on one thread, with the parallel
+                              feature
+  --cache <file>              look layers up in a search cache written by
+                              `reachability build`
+
+Without shape arguments the shapes are read from standard input, one
+operation per line. Without an expression, eval runs standard input line
+by line, keeping the variables assigned by earlier lines.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args[..] {
+        ["solve", ref args @ ..] => solve(args),
+        ["rotate", direction, ref shapes @ ..] => match direction {
+            "cw" => each(shapes, |shape| {
+                Ok(show(&shape.rotate(RotateDirection::Clockwise, 1)))
+            }),
+            "ccw" => each(shapes, |shape| {
+                Ok(show(&shape.rotate(RotateDirection::CounterClockwise, 1)))
+            }),
+            "180" => each(shapes, |shape| Ok(show(&shape.rotate_180()))),
+            _ => Err(format!(
+                "unknown direction {:?}, expected cw, ccw or 180",
+                direction
+            )),
+        },
+        ["cut", ref shapes @ ..] => each(shapes, |shape| {
+            let [west, east] = shape.cutting();
+            Ok(format!("{} {}", show(&west), show(&east)))
+        }),
+        ["swap", ref shapes @ ..] => per_line(shapes, |shapes| match shapes {
+            [a, b] => {
+                let [a, b] = Shape::swap(a, b);
+                Ok(format!("{} {}", show(&a), show(&b)))
+            }
+            _ => Err(format!("swap takes 2 shapes, got {}", shapes.len())),
+        }),
+        ["stack", ref shapes @ ..] => per_line(shapes, |shapes| match shapes {
+            [bottom, tops @ ..] if !tops.is_empty() => Ok(show(
+                &tops
+                    .iter()
+                    .fold(*bottom, |shape, top| shape.stack_with(top)),
+            )),
+            _ => Err(format!(
+                "stack takes at least 2 shapes, got {}",
+                shapes.len()
+            )),
+        }),
+        ["viewer-url", ref shapes @ ..] => {
+            each(shapes, |shape| Ok(shape.to_shapez2_shape_viewer()))
+        }
+        ["render", ref shapes @ ..] => each(shapes, |shape| {
+            Ok(format!("{}\n{}", show(shape), render_terminal(shape)))
+        }),
+        ["svg", ref shapes @ ..] => each(shapes, |shape| Ok(render_svg(shape))),
+        ["random"] => random("1"),
+        ["random", count] => random(count),
+        ["validate", ref shapes @ ..] => each(shapes, |shape| {
+            let reasons = analyze(shape);
+            if reasons.is_empty() {
+                return Ok(format!("{}: ok", show(shape)));
+            }
+            let reasons: Vec<String> = reasons.iter().map(|reason| reason.to_string()).collect();
+            Err(format!("impossible to build\n- {}", reasons.join("\n- ")))
+        }),
+        ["normalize", ref shapes @ ..] => each(shapes, |shape| Ok(show(shape))),
+        ["eval", ref source @ ..] => eval(source),
+        ["repl"] => repl(),
+        ["help" | "--help" | "-h"] => {
+            println!("{}", USAGE);
+            Ok(true)
+        }
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
     }
 }
 
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+/// Shape identifier for output, the empty shape included
+fn show(shape: &Shape) -> String {
+    if shape.layer_height() == 0 {
+        return SingleLayer::default().to_string();
     }
+    shape.to_string()
 }
 
-fn heuristic(layer: &SingleLayer, goal: &SingleLayer) -> usize {
-    let mut count = 0;
-    for j in 0..SHAPEZ2_DEMENTION {
-        let item_s = layer.items[j];
-        let item_g = goal.items[j];
-        if item_s.shape == item_g.shape {
-            count += 1;
-        }
-        if item_s.color == item_g.color {
-            count += 1;
+/// The given shapes as a single line, or else the lines of standard input
+/// split at whitespace
+fn input_lines(args: &[&str]) -> Result<Vec<Vec<String>>, String> {
+    if !args.is_empty() {
+        return Ok(vec![args.iter().map(|arg| arg.to_string()).collect()]);
+    }
+    let mut lines = Vec::new();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|err| format!("stdin: {}", err))?;
+        let words: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+        if !words.is_empty() {
+            lines.push(words);
         }
     }
-
-    count
+    Ok(lines)
 }
 
-fn a_star(start_shapes: &[SingleLayer], goal: &SingleLayer) -> Option<Vec<SingleLayer>> {
-    let mut open_set = BinaryHeap::new();
-    let mut closed_set = HashSet::new();
-
-    for layer in start_shapes {
-        let initial_state = State {
-            layer: layer.clone(),
-            path: vec![layer.clone()],
-            cost: 0,
-            heuristic: heuristic(layer, goal),
-        };
-        open_set.push(initial_state);
+/// Run `operation` on the shapes of every input line and print the results.
+/// Failures are reported on stderr and don't stop the remaining lines, the
+/// result tells whether every line succeeded.
+fn per_line(
+    args: &[&str],
+    operation: impl Fn(&[Shape]) -> Result<String, String>,
+) -> Result<bool, String> {
+    let mut ok = true;
+    for line in input_lines(args)? {
+        let result = line
+            .iter()
+            .map(|word| Shape::try_from_string(word).map_err(|err| err.to_string()))
+            .collect::<Result<Vec<Shape>, String>>()
+            .and_then(|shapes| operation(&shapes));
+        match result {
+            Ok(output) => println!("{}", output),
+            Err(err) => {
+                eprintln!("{}: {}", line.join(" "), err);
+                ok = false;
+            }
+        }
     }
+    Ok(ok)
+}
 
-    let mut cnt = 0;
+/// Like `per_line`, with every shape handled on its own
+fn each(
+    args: &[&str],
+    operation: impl Fn(&Shape) -> Result<String, String>,
+) -> Result<bool, String> {
+    let words: Vec<String> = input_lines(args)?.into_iter().flatten().collect();
+    let mut ok = true;
+    for word in words {
+        ok &= per_line(&[&word], |shapes| operation(&shapes[0]))?;
+    }
+    Ok(ok)
+}
 
-    while let Some(current) = open_set.pop() {
-        if current.layer == *goal {
-            return Some(current.path);
-        }
+fn solve(args: &[&str]) -> Result<bool, String> {
+    let (solver, shapes) = solver_options(args)?;
+    each(shapes, |shape| {
+        solver
+            .solve(shape)
+            .map(|plan| plan.to_string())
+            .map_err(|err| err.to_string())
+    })
+}
 
-        if closed_set.contains(&current.layer) {
-            continue;
-        }
-        closed_set.insert(current.layer.clone());
-
-        let new_states = vec![
-            current.layer.rotate_once(),
-            current.layer.rotate_once_reverse(),
-            current.layer.rotate_180(),
-            current.layer.cutting()[0],
-            current.layer.cutting()[1],
-        ];
-
-        for new_state in new_states {
-            if !closed_set.contains(&new_state) {
-                let mut new_path = current.path.clone();
-                new_path.push(new_state.clone());
-                let new_cost = current.cost + 1;
-                let new_heuristic = heuristic(&new_state, goal);
-                let next_state = State {
-                    layer: new_state,
-                    path: new_path,
-                    cost: new_cost,
-                    heuristic: new_heuristic,
-                };
-                open_set.push(next_state);
+/// The solver set up by the options in front of the shapes, and the shapes
+fn solver_options<'a>(mut args: &'a [&'a str]) -> Result<(Solver, &'a [&'a str]), String> {
+    let mut solver = Solver::new();
+    loop {
+        match args {
+            ["--cache", path, rest @ ..] => {
+                let file = File::open(path).map_err(|err| format!("{}: {}", path, err))?;
+                let cache = SearchCache::read_from(BufReader::new(file))
+                    .map_err(|err| format!("{}: {}", path, err))?;
+                solver = solver.search_cache(Arc::new(cache));
+                args = rest;
+            }
+            ["--max-nodes", count, rest @ ..] => {
+                solver = solver.max_nodes(parse_value("--max-nodes", count)?);
+                args = rest;
+            }
+            ["--time-limit", value, rest @ ..] => {
+                let seconds = parse_value("--time-limit", value)?;
+                let limit = Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| format!("invalid value {:?} for --time-limit", value))?;
+                solver = solver.time_limit(limit);
+                args = rest;
+            }
+            ["--objective", name, rest @ ..] => {
+                solver = solver.cost_model(CostModel::new(objective(name)?));
+                args = rest;
+            }
+            ["--bidirectional", rest @ ..] => {
+                solver = solver.bidirectional(true);
+                args = rest;
+            }
+            [option @ ("--cache" | "--max-nodes" | "--time-limit" | "--objective" | "--threads")] => {
+                return Err(format!("{} needs a value", option))
             }
+            #[cfg(feature = "parallel")]
+            ["--threads", count, rest @ ..] => {
+                solver = solver.threads(parse_value("--threads", count)?);
+                args = rest;
+            }
+            #[cfg(not(feature = "parallel"))]
+            ["--threads", ..] => {
+                return Err("--threads needs a build with the parallel feature".to_string())
+            }
+            [option, ..] if option.starts_with("--") => {
+                return Err(format!("unknown option {:?}", option))
+            }
+            _ => return Ok((solver, args)),
         }
+    }
+}
 
-        for shape in start_shapes {
-            let [swap_a, swap_b] = current.layer.swap_with(shape);
-            if !closed_set.contains(&swap_a) {
-                let mut new_path = current.path.clone();
-                new_path.push(swap_a.clone());
-                let new_cost = current.cost + 1;
-                let new_heuristic = heuristic(&swap_a, goal);
-                let next_state = State {
-                    layer: swap_a,
-                    path: new_path,
-                    cost: new_cost,
-                    heuristic: new_heuristic,
-                };
-                open_set.push(next_state);
-            }
-            if !closed_set.contains(&swap_b) {
-                let mut new_path = current.path.clone();
-                new_path.push(swap_b.clone());
-                let new_cost = current.cost + 1;
-                let new_heuristic = heuristic(&swap_b, goal);
-                let next_state = State {
-                    layer: swap_b,
-                    path: new_path,
-                    cost: new_cost,
-                    heuristic: new_heuristic,
-                };
-                open_set.push(next_state);
-            }
+fn parse_value<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {:?} for {}", value, option))
+}
+
+fn objective(name: &str) -> Result<Objective, String> {
+    match name {
+        "fewest-machines" => Ok(Objective::FewestMachines),
+        "weighted" => Ok(Objective::Weighted),
+        "fewest-building-types" => Ok(Objective::FewestBuildingTypes),
+        "smallest-footprint" => Ok(Objective::SmallestFootprint),
+        "lowest-power" => Ok(Objective::LowestPower),
+        _ => Err(format!("unknown objective {:?}", name)),
+    }
+}
+
+fn random(count: &str) -> Result<bool, String> {
+    let count: usize = count
+        .parse()
+        .map_err(|_| format!("invalid count {:?}", count))?;
+    for _ in 0..count {
+        println!("{}", random_shape());
+    }
+    Ok(true)
+}
+
+/// A random shape that `analyze` finds nothing wrong with, so `validate`
+/// accepts it
+fn random_shape() -> Shape {
+    loop {
+        let height = rand::random::<usize>() % SHAPEZ2_LAYER + 1;
+        let shape = Shape::random_with_height(height).collapse();
+        if analyze(&shape).is_empty() {
+            return shape;
         }
+    }
+}
 
-        cnt += 1;
-        if cnt % 1000 == 0 {
-            eprintln!("cnt: {}", cnt);
+fn eval(source: &[&str]) -> Result<bool, String> {
+    let mut evaluator = Evaluator::new();
+    if !source.is_empty() {
+        let outputs = evaluator
+            .eval(&source.join(" "))
+            .map_err(|err| err.to_string())?;
+        print_outputs(&outputs);
+        return Ok(true);
+    }
+    let mut ok = true;
+    for (i, line) in std::io::stdin().lock().lines().enumerate() {
+        let line = line.map_err(|err| format!("stdin: {}", err))?;
+        match evaluator.eval(&line) {
+            Ok(outputs) => print_outputs(&outputs),
+            Err(err) => {
+                eprintln!("line {}: {}", i + 1, err);
+                ok = false;
+            }
         }
     }
+    Ok(ok)
+}
 
-    None
+fn print_outputs(outputs: &[Shape]) {
+    if !outputs.is_empty() {
+        let outputs: Vec<String> = outputs.iter().map(show).collect();
+        println!("{}", outputs.join(" "));
+    }
 }
 
-fn main() {
-    let goal_shape = Shape::try_from_string("Sb----Wm:--CcP-P-:--P-----:--Sc--Sg").unwrap();
-    let needed_shapes = needed_layers(goal_shape.unique_flat_items());
-    println!(
-        "Goal shape: {}\n{}",
-        goal_shape,
-        goal_shape.to_shapez2_shape_viewer()
-    );
-    needed_shapes.iter().for_each(|shape| {
-        println!("Needed shape: {}", shape,);
-    });
-
-    let mut final_path = Vec::new();
-    goal_shape.into_iter().for_each(|layer| {
-        if let Some(path) = a_star(&needed_shapes, &layer) {
-            final_path.extend(path);
-        } else {
-            println!("No path found for layer: {:?}", layer);
-            return;
+fn repl() -> Result<bool, String> {
+    let mut workspace = Workspace::new();
+    let mut stdin = std::io::stdin().lock();
+    println!("shapez2-calc repl, :help for help");
+    loop {
+        print!("> ");
+        std::io::stdout().flush().map_err(|err| err.to_string())?;
+        let mut line = String::new();
+        if stdin.read_line(&mut line).map_err(|err| err.to_string())? == 0 {
+            println!();
+            return Ok(true);
         }
-    });
+        match workspace.execute(&line) {
+            Ok(Reply::Shapes(shapes)) => {
+                for shape in shapes.iter() {
+                    print_rendered(show(shape), shape);
+                }
+            }
+            Ok(Reply::Variables(variables)) => {
+                for (name, shapes) in variables.iter() {
+                    for shape in shapes.iter() {
+                        print_rendered(format!("{} = {}", name, show(shape)), shape);
+                    }
+                }
+            }
+            Ok(Reply::Text(text)) => {
+                if !text.is_empty() {
+                    println!("{}", text);
+                }
+            }
+            Ok(Reply::Quit) => return Ok(true),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+}
 
-    println!("Final path: {:?}", final_path);
+fn print_rendered(label: String, shape: &Shape) {
+    println!("{}", label);
+    if shape.layer_height() > 0 {
+        println!("{}", render_terminal(shape));
+    }
 }
@@ -0,0 +1,387 @@
+use crate::{
+    cutting::Cuttable,
+    rotate::Rotatable,
+    shape::{EShape, Shape, SingleItem, SingleLayer, SHAPEZ2_DEMENTION, SHAPEZ2_LAYER},
+    stack::Stackable,
+};
+use std::fmt::Display;
+
+const PART_BITS: u32 = SingleItem::BITS;
+const PART_MASK: u32 = (1 << PART_BITS) - 1;
+const LAYER_BITS: u32 = PART_BITS * SHAPEZ2_DEMENTION as u32;
+const LAYER_MASK: u32 = (1 << LAYER_BITS) - 1;
+/// Packed parts are xor-ed with the empty item so that empty parts are 0
+const EMPTY_BITS: u8 = 0b1001110;
+/// Low bits of a packed part holding the shape
+const SHAPE_MASK: u32 = 0b111;
+
+/// Packed code of a part with the given shape, ignoring the color
+const fn shape_code(shape: EShape) -> u32 {
+    (shape as u32 ^ EMPTY_BITS as u32) & SHAPE_MASK
+}
+
+fn pack_item(item: &SingleItem) -> u32 {
+    (item.to_bits() ^ EMPTY_BITS) as u32
+}
+
+fn unpack_item(bits: u32) -> Option<SingleItem> {
+    SingleItem::from_bits(bits as u8 ^ EMPTY_BITS)
+}
+
+/// Bits `0..PART_BITS` of every part selected by `parts`, repeated in each
+/// of `layers` layers
+const fn part_mask(parts: &[usize], layers: usize) -> u128 {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < layers {
+        let mut k = 0;
+        while k < parts.len() {
+            mask |= (PART_MASK as u128) << (i as u32 * LAYER_BITS + parts[k] as u32 * PART_BITS);
+            k += 1;
+        }
+        i += 1;
+    }
+    mask
+}
+
+const ALL_PARTS: u128 = part_mask(&[0, 1, 2, 3], SHAPEZ2_LAYER);
+const FIRST_PART: u128 = part_mask(&[0], SHAPEZ2_LAYER);
+const LAST_PART: u128 = part_mask(&[3], SHAPEZ2_LAYER);
+const EAST_HALF: u128 = part_mask(&[0, 1], SHAPEZ2_LAYER);
+const WEST_HALF: u128 = part_mask(&[2, 3], SHAPEZ2_LAYER);
+
+/// A single quad layer packed into 7 bits per part
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct LayerId(u32);
+
+impl LayerId {
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// `None` if some part doesn't decode or bits above the layer are set
+    pub fn from_bits(bits: u32) -> Option<LayerId> {
+        if bits & !LAYER_MASK != 0 {
+            return None;
+        }
+        for j in 0..SHAPEZ2_DEMENTION as u32 {
+            unpack_item((bits >> (j * PART_BITS)) & PART_MASK)?;
+        }
+        Some(LayerId(bits))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl From<SingleLayer> for LayerId {
+    fn from(layer: SingleLayer) -> Self {
+        let mut bits = 0;
+        for (j, item) in layer.items.iter().enumerate() {
+            bits |= pack_item(item) << (j as u32 * PART_BITS);
+        }
+        LayerId(bits)
+    }
+}
+
+impl From<LayerId> for SingleLayer {
+    fn from(id: LayerId) -> Self {
+        let mut layer = SingleLayer::default();
+        for j in 0..SHAPEZ2_DEMENTION {
+            let bits = (id.0 >> (j as u32 * PART_BITS)) & PART_MASK;
+            layer.items[j] = unpack_item(bits).unwrap_or_default();
+        }
+        layer
+    }
+}
+
+impl Display for LayerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", SingleLayer::from(*self))
+    }
+}
+
+/// A whole quad shape packed into a `u128`, layer 0 in the lowest bits.
+///
+/// Converting to and from `Shape` is lossless. Rotating, cutting, swapping
+/// and stacking work on the bits directly, falling back to `Shape` only when
+/// crystals are involved or parts have to fall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct ShapeId(u128);
+
+impl ShapeId {
+    pub fn bits(&self) -> u128 {
+        self.0
+    }
+
+    /// `None` if some part doesn't decode or bits above the last layer are set
+    pub fn from_bits(bits: u128) -> Option<ShapeId> {
+        if bits & !ALL_PARTS != 0 {
+            return None;
+        }
+        for i in 0..SHAPEZ2_LAYER {
+            LayerId::from_bits(Self(bits).layer_bits(i))?;
+        }
+        Some(ShapeId(bits))
+    }
+
+    fn layer_bits(&self, layer: usize) -> u32 {
+        (self.0 >> (layer as u32 * LAYER_BITS)) as u32 & LAYER_MASK
+    }
+
+    pub fn layer(&self, layer: usize) -> LayerId {
+        LayerId(self.layer_bits(layer))
+    }
+
+    pub fn layer_height(&self) -> usize {
+        (0..SHAPEZ2_LAYER)
+            .rev()
+            .find(|&i| self.occupied(i) != 0)
+            .map_or(0, |i| i + 1)
+    }
+
+    /// One bit per part of `layer` holding something
+    fn occupied(&self, layer: usize) -> u32 {
+        self.parts_where(layer, |code| code != 0)
+    }
+
+    /// One bit per part of `layer` whose shape code passes `test`
+    fn parts_where(&self, layer: usize, test: impl Fn(u32) -> bool) -> u32 {
+        let bits = self.layer_bits(layer);
+        (0..SHAPEZ2_DEMENTION as u32)
+            .filter(|&j| test((bits >> (j * PART_BITS)) & SHAPE_MASK))
+            .fold(0, |mask, j| mask | 1 << j)
+    }
+
+    fn has_crystals(&self) -> bool {
+        (0..SHAPEZ2_LAYER)
+            .any(|i| self.parts_where(i, |code| code == shape_code(EShape::Crystal)) != 0)
+    }
+
+    /// Whether no part would fall, for shapes without crystals. Without
+    /// crystals groups never span layers, so support can be worked out
+    /// layer by layer from the ground up.
+    fn is_stable_without_crystals(&self) -> bool {
+        let n = SHAPEZ2_DEMENTION as u32;
+        let rotl = |mask: u32| ((mask << 1) | (mask >> (n - 1))) & ((1 << n) - 1);
+        let rotr = |mask: u32| ((mask >> 1) | (mask << (n - 1))) & ((1 << n) - 1);
+        for i in 1..SHAPEZ2_LAYER {
+            let occupied = self.occupied(i);
+            let sticky = self.parts_where(i, |code| code != 0 && code != shape_code(EShape::Pin));
+            let mut supported = occupied & self.occupied(i - 1);
+            loop {
+                let spread =
+                    supported | ((rotl(supported & sticky) | rotr(supported & sticky)) & sticky);
+                if spread == supported {
+                    break;
+                }
+                supported = spread;
+            }
+            if supported != occupied {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Use the bit result of an operation on crystal-free inputs if no part
+    /// has to fall, otherwise redo the operation on `Shape`
+    fn settled_or(self, crystals: bool, fallback: impl FnOnce() -> Shape) -> ShapeId {
+        if !crystals && self.is_stable_without_crystals() {
+            self
+        } else {
+            ShapeId::from(fallback())
+        }
+    }
+}
+
+impl From<Shape> for ShapeId {
+    fn from(shape: Shape) -> Self {
+        let mut bits = 0;
+        for i in 0..SHAPEZ2_LAYER {
+            bits |= (LayerId::from(shape[i]).0 as u128) << (i as u32 * LAYER_BITS);
+        }
+        ShapeId(bits)
+    }
+}
+
+impl From<ShapeId> for Shape {
+    fn from(id: ShapeId) -> Self {
+        let mut shape = Shape::default();
+        for i in 0..SHAPEZ2_LAYER {
+            shape[i] = id.layer(i).into();
+        }
+        shape
+    }
+}
+
+impl Display for ShapeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Shape::from(*self))
+    }
+}
+
+impl Rotatable for ShapeId {
+    const PARTS: usize = SHAPEZ2_DEMENTION;
+
+    fn rotate_once(&self) -> ShapeId {
+        let moved = (self.0 << PART_BITS) & ALL_PARTS & !FIRST_PART;
+        let wrapped = (self.0 >> (LAYER_BITS - PART_BITS)) & FIRST_PART;
+        ShapeId(moved | wrapped)
+    }
+
+    fn rotate_once_reverse(&self) -> ShapeId {
+        let moved = (self.0 >> PART_BITS) & !LAST_PART;
+        let wrapped = (self.0 << (LAYER_BITS - PART_BITS)) & LAST_PART;
+        ShapeId(moved | wrapped)
+    }
+
+    fn rotate_180(&self) -> ShapeId {
+        let half = 2 * PART_BITS;
+        let up = (self.0 << half) & WEST_HALF;
+        let down = (self.0 >> half) & EAST_HALF;
+        ShapeId(up | down)
+    }
+}
+
+impl Cuttable for ShapeId {
+    fn half_destroyed(&mut self) {
+        let shape = *self;
+        *self = ShapeId(shape.0 & EAST_HALF)
+            .settled_or(shape.has_crystals(), || Shape::from(shape).half_destroy());
+    }
+
+    fn swapd(a: &mut Self, b: &mut Self) {
+        let (ori_a, ori_b) = (*a, *b);
+        let crystals = ori_a.has_crystals() || ori_b.has_crystals();
+        let fallback = || Shape::swap(&ori_a.into(), &ori_b.into());
+        *a = ShapeId((ori_a.0 & EAST_HALF) | (ori_b.0 & WEST_HALF))
+            .settled_or(crystals, || fallback()[0]);
+        *b = ShapeId((ori_b.0 & EAST_HALF) | (ori_a.0 & WEST_HALF))
+            .settled_or(crystals, || fallback()[1]);
+    }
+}
+
+impl Stackable for ShapeId {
+    fn stacked_with(&mut self, other_on_top: &Self) {
+        let bottom = *self;
+        let height = bottom.layer_height();
+        let fallback = || Shape::stack(&bottom.into(), &(*other_on_top).into());
+        *self = if height + other_on_top.layer_height() > SHAPEZ2_LAYER {
+            ShapeId::from(fallback())
+        } else {
+            let crystals = bottom.has_crystals() || other_on_top.has_crystals();
+            ShapeId(bottom.0 | other_on_top.0 << (height as u32 * LAYER_BITS))
+                .settled_or(crystals, fallback)
+        };
+    }
+}
+
+/// A layer is a shape with nothing above the ground layer
+impl Rotatable for LayerId {
+    const PARTS: usize = SHAPEZ2_DEMENTION;
+
+    fn rotate_once(&self) -> LayerId {
+        ShapeId(self.0 as u128).rotate_once().layer(0)
+    }
+
+    fn rotate_once_reverse(&self) -> LayerId {
+        ShapeId(self.0 as u128).rotate_once_reverse().layer(0)
+    }
+}
+
+impl Cuttable for LayerId {
+    fn half_destroyed(&mut self) {
+        *self = ShapeId(self.0 as u128).half_destroy().layer(0);
+    }
+
+    fn swapd(a: &mut Self, b: &mut Self) {
+        let [new_a, new_b] = ShapeId::swap(&ShapeId(a.0 as u128), &ShapeId(b.0 as u128));
+        *a = new_a.layer(0);
+        *b = new_b.layer(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{shape, EColor};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_empty_bits() {
+        assert_eq!(SingleItem::default().to_bits(), EMPTY_BITS);
+        assert_eq!(ShapeId::from(Shape::default()).bits(), 0);
+        assert_eq!(shape_code(EShape::Empty), 0,);
+    }
+
+    #[test]
+    fn test_loopback() {
+        for _ in 0..100 {
+            let ori = Shape::random();
+            let id = ShapeId::from(ori);
+            assert_eq!(Shape::from(id), ori);
+            assert_eq!(ShapeId::from_bits(id.bits()), Some(id));
+            assert_eq!(id.layer_height(), ori.layer_height());
+        }
+        assert_eq!(ShapeId::from_bits(u128::MAX), None);
+        assert_eq!(ShapeId::from_bits(0b0000001), None);
+    }
+
+    #[test]
+    fn test_operations_match_shape() {
+        let mut shapes: Vec<Shape> = (0..200).map(|_| Shape::random()).collect();
+        shapes.extend(
+            [
+                "CuCuCuCu:RrRr----",
+                "Cu------:--Cu----",
+                "P-P-----:CuCu----",
+                "crcr----:crcr----",
+                "Cucrcr--",
+            ]
+            .map(shape),
+        );
+        for a in shapes.iter() {
+            let id = ShapeId::from(*a);
+            assert_eq!(Shape::from(id.rotate_once()), a.rotate_once());
+            assert_eq!(
+                Shape::from(id.rotate_once_reverse()),
+                a.rotate_once_reverse()
+            );
+            assert_eq!(Shape::from(id.rotate_180()), a.rotate_180());
+            assert_eq!(id.cutting().map(Shape::from), a.cutting());
+            for b in shapes.iter().take(20) {
+                let other = ShapeId::from(*b);
+                assert_eq!(
+                    ShapeId::swap(&id, &other).map(Shape::from),
+                    Shape::swap(a, b)
+                );
+                assert_eq!(Shape::from(ShapeId::stack(&id, &other)), Shape::stack(a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn test_layer_id() {
+        let layer = SingleLayer::new_with_shape_color(EShape::Star, EColor::Blue);
+        let id = LayerId::from(layer);
+        assert_eq!(SingleLayer::from(id), layer);
+        assert_eq!(LayerId::from_bits(id.bits()), Some(id));
+        assert!(LayerId::from(SingleLayer::default()).is_empty());
+        assert_eq!(id.to_string(), "SbSbSbSb");
+
+        for _ in 0..100 {
+            let a = rand::random::<SingleLayer>();
+            let b = rand::random::<SingleLayer>();
+            let (id_a, id_b) = (LayerId::from(a), LayerId::from(b));
+            assert_eq!(SingleLayer::from(id_a.rotate_once()), a.rotate_once());
+            assert_eq!(SingleLayer::from(id_a.rotate_180()), a.rotate_180());
+            assert_eq!(id_a.cutting().map(SingleLayer::from), a.cutting());
+            assert_eq!(
+                LayerId::swap(&id_a, &id_b).map(SingleLayer::from),
+                SingleLayer::swap(&a, &b)
+            );
+        }
+    }
+}
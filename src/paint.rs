@@ -0,0 +1,111 @@
+use crate::shape::{EColor, EShape, GenericLayer, GenericShape};
+
+/// Painting only recolors real shape parts: pins, crystals and empty slots
+/// don't take paint
+pub trait Paintable: Sized + Copy {
+    /// paint a single quadrant of the top layer, panics if `quadrant` is
+    /// not a part of the layer
+    fn painted_quadrant(&mut self, quadrant: usize, color: EColor);
+    fn painted_top(&mut self, color: EColor);
+    fn painted_all(&mut self, color: EColor);
+
+    fn paint_quadrant(&self, quadrant: usize, color: EColor) -> Self {
+        let mut shape = *self;
+        shape.painted_quadrant(quadrant, color);
+        shape
+    }
+    fn paint_top(&self, color: EColor) -> Self {
+        let mut shape = *self;
+        shape.painted_top(color);
+        shape
+    }
+    fn paint_all(&self, color: EColor) -> Self {
+        let mut shape = *self;
+        shape.painted_all(color);
+        shape
+    }
+}
+
+fn takes_paint(shape: EShape) -> bool {
+    !matches!(shape, EShape::Empty | EShape::Pin | EShape::Crystal)
+}
+
+impl<const N: usize> Paintable for GenericLayer<N> {
+    fn painted_quadrant(&mut self, quadrant: usize, color: EColor) {
+        assert!(quadrant < N, "quadrant {} out of range 0..{}", quadrant, N);
+        let item = &mut self.items[quadrant];
+        if takes_paint(item.shape) {
+            item.color = color;
+        }
+    }
+
+    fn painted_top(&mut self, color: EColor) {
+        for j in 0..N {
+            self.painted_quadrant(j, color);
+        }
+    }
+
+    fn painted_all(&mut self, color: EColor) {
+        self.painted_top(color);
+    }
+}
+
+impl<const N: usize, const L: usize> Paintable for GenericShape<N, L> {
+    fn painted_quadrant(&mut self, quadrant: usize, color: EColor) {
+        assert!(quadrant < N, "quadrant {} out of range 0..{}", quadrant, N);
+        if let Some(top) = self.layer_height().checked_sub(1) {
+            self[top].painted_quadrant(quadrant, color);
+        }
+    }
+
+    fn painted_top(&mut self, color: EColor) {
+        if let Some(top) = self.layer_height().checked_sub(1) {
+            self[top].painted_top(color);
+        }
+    }
+
+    fn painted_all(&mut self, color: EColor) {
+        for i in 0..L {
+            self[i].painted_all(color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{shape, SingleLayer};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_paint_top() {
+        let ori = shape("CuCuCuCu:RuP-cb--");
+        assert_eq!(ori.paint_top(EColor::Red), shape("CuCuCuCu:RrP-cb--"));
+        assert_eq!(ori.paint_all(EColor::Red), shape("CrCrCrCr:RrP-cb--"));
+    }
+
+    #[test]
+    fn test_paint_quadrant() {
+        let ori = shape("CuCuCuCu:RuRu----");
+        assert_eq!(
+            ori.paint_quadrant(1, EColor::Green),
+            shape("CuCuCuCu:RuRg----")
+        );
+        assert_eq!(ori.paint_quadrant(2, EColor::Green), ori);
+    }
+
+    #[test]
+    #[should_panic(expected = "quadrant 4 out of range 0..4")]
+    fn test_paint_quadrant_out_of_range() {
+        shape("CuCuCuCu").paint_quadrant(4, EColor::Green);
+    }
+
+    #[test]
+    fn test_paint_layer() {
+        let layer = SingleLayer::try_from_string("Cu--P-Sb").unwrap();
+        assert_eq!(
+            layer.paint_top(EColor::Yellow),
+            SingleLayer::try_from_string("Cy--P-Sy").unwrap()
+        );
+    }
+}
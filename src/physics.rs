@@ -0,0 +1,283 @@
+use crate::shape::{EShape, GenericLayer, GenericShape};
+
+/// Position of a part inside a shape, as `(layer, part)`
+pub type Position = (usize, usize);
+
+/// A set of parts that stick together and therefore fall together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub parts: Vec<Position>,
+    pub supported: bool,
+}
+
+pub trait Physics: Sized + Copy {
+    fn collapsed(&mut self);
+    fn collapse(&self) -> Self {
+        let mut shape = *self;
+        shape.collapsed();
+        shape
+    }
+    fn groups(&self) -> Vec<Group>;
+    fn is_stable(&self) -> bool {
+        self.groups().iter().all(|group| group.supported)
+    }
+}
+
+impl<const N: usize, const L: usize> Physics for GenericShape<N, L> {
+    fn collapsed(&mut self) {
+        collapse(&mut self.items);
+    }
+
+    fn groups(&self) -> Vec<Group> {
+        groups(&self.items)
+    }
+}
+
+fn neighbours<const N: usize>(j: usize) -> [usize; 2] {
+    [(j + 1) % N, (j + N - 1) % N]
+}
+
+/// Two horizontally adjacent parts stick together unless one of them is a pin
+fn connected(a: EShape, b: EShape) -> bool {
+    !matches!(a, EShape::Empty | EShape::Pin) && !matches!(b, EShape::Empty | EShape::Pin)
+}
+
+/// Parts stuck to the part at `(l, p)`: its horizontal neighbours and, for
+/// crystals, the crystals right above and below it
+fn linked_parts<const N: usize>(
+    layers: &[GenericLayer<N>],
+    (l, p): Position,
+    crystals_only: bool,
+) -> Vec<Position> {
+    let sticks = |a: EShape, b: EShape| match crystals_only {
+        true => a == EShape::Crystal && b == EShape::Crystal,
+        false => connected(a, b),
+    };
+    let shape = layers[l][p].shape;
+    let mut linked: Vec<Position> = neighbours::<N>(p)
+        .into_iter()
+        .filter(|&k| sticks(shape, layers[l][k].shape))
+        .map(|k| (l, k))
+        .collect();
+    if shape == EShape::Crystal {
+        if l > 0 && layers[l - 1][p].shape == EShape::Crystal {
+            linked.push((l - 1, p));
+        }
+        if l + 1 < layers.len() && layers[l + 1][p].shape == EShape::Crystal {
+            linked.push((l + 1, p));
+        }
+    }
+    linked
+}
+
+/// Flood fill the parts reachable from `start` through `linked_parts`
+fn flood<const N: usize>(
+    layers: &[GenericLayer<N>],
+    start: Position,
+    crystals_only: bool,
+    visited: &mut [[bool; N]],
+) -> Vec<Position> {
+    let mut parts = vec![start];
+    visited[start.0][start.1] = true;
+    let mut next = 0;
+    while next < parts.len() {
+        for (l, k) in linked_parts(layers, parts[next], crystals_only) {
+            if !visited[l][k] {
+                visited[l][k] = true;
+                parts.push((l, k));
+            }
+        }
+        next += 1;
+    }
+    parts
+}
+
+/// Split the parts of `layers` into connected groups and work out which of
+/// them are supported.
+///
+/// A group is supported when one of its parts stands on the ground or right
+/// above a part of a supported group.
+pub fn groups<const N: usize>(layers: &[GenericLayer<N>]) -> Vec<Group> {
+    let mut group_of = vec![[None; N]; layers.len()];
+    let mut visited = vec![[false; N]; layers.len()];
+    let mut groups: Vec<Group> = Vec::new();
+
+    for i in 0..layers.len() {
+        for j in 0..N {
+            if layers[i][j].shape == EShape::Empty || visited[i][j] {
+                continue;
+            }
+            let parts = flood(layers, (i, j), false, &mut visited);
+            for &(l, p) in parts.iter() {
+                group_of[l][p] = Some(groups.len());
+            }
+            groups.push(Group {
+                parts,
+                supported: false,
+            });
+        }
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in 0..groups.len() {
+            if groups[index].supported {
+                continue;
+            }
+            let supported = groups[index].parts.iter().any(|&(l, p)| {
+                l == 0 || group_of[l - 1][p].is_some_and(|below| groups[below].supported)
+            });
+            if supported {
+                groups[index].supported = true;
+                changed = true;
+            }
+        }
+    }
+    groups
+}
+
+/// Let every unsupported group fall one layer at a time until everything
+/// rests on something. Crystals shatter as soon as they start falling.
+pub fn collapse<const N: usize>(layers: &mut [GenericLayer<N>]) {
+    loop {
+        let mut falling: Vec<Position> = groups(layers)
+            .into_iter()
+            .filter(|group| !group.supported)
+            .flat_map(|group| group.parts)
+            .collect();
+        if falling.is_empty() {
+            break;
+        }
+        let crystals: Vec<Position> = falling
+            .iter()
+            .copied()
+            .filter(|&(l, p)| layers[l][p].shape == EShape::Crystal)
+            .collect();
+        if !crystals.is_empty() {
+            for (l, p) in crystals {
+                layers[l][p] = Default::default();
+            }
+            continue;
+        }
+        // move the lowest parts first so nothing is overwritten
+        falling.sort();
+        for (l, p) in falling {
+            layers[l - 1][p] = layers[l][p];
+            layers[l][p] = Default::default();
+        }
+    }
+}
+
+/// Shatter every crystal that is stuck, directly or through other crystals,
+/// to a crystal on the other side of the east/west cut line.
+pub fn shatter_cut_crystals<const N: usize>(layers: &mut [GenericLayer<N>]) {
+    let mut visited = vec![[false; N]; layers.len()];
+    for i in 0..layers.len() {
+        for j in 0..N {
+            if layers[i][j].shape != EShape::Crystal || visited[i][j] {
+                continue;
+            }
+            let crystals = flood(layers, (i, j), true, &mut visited);
+            let west = |&(_, p): &Position| p * 2 / N > 0;
+            if crystals.iter().any(west) && !crystals.iter().all(west) {
+                for (l, p) in crystals {
+                    layers[l][p] = Default::default();
+                }
+            }
+        }
+    }
+}
+
+/// Collapse an arbitrary tall stack of layers into a shape, discarding
+/// everything above the shape's layer limit `L`.
+pub fn settle<const N: usize, const L: usize>(
+    mut layers: Vec<GenericLayer<N>>,
+) -> GenericShape<N, L> {
+    collapse(&mut layers);
+    let mut shape = GenericShape::default();
+    for (i, layer) in layers.into_iter().take(L).enumerate() {
+        shape[i] = layer;
+    }
+    shape
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{shape, HexShape, Shape};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_groups() {
+        let groups = shape("CuCu--P-:----Cu--").groups();
+        assert_eq!(
+            groups,
+            vec![
+                Group {
+                    parts: vec![(0, 0), (0, 1)],
+                    supported: true,
+                },
+                Group {
+                    parts: vec![(0, 3)],
+                    supported: true,
+                },
+                Group {
+                    parts: vec![(1, 2)],
+                    supported: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_support_through_neighbours() {
+        assert!(shape("Cu------:CuCuCuCu:----Cu--").is_stable());
+        assert!(!shape("Cu------:--Cu----").is_stable());
+        assert!(!shape("Cu------:P-P-----").is_stable());
+    }
+
+    #[test]
+    fn test_collapse() {
+        assert_eq!(shape("Cu------:--Cu----").collapse(), shape("CuCu----"));
+        assert_eq!(
+            shape("Cu------:--------:--CuCu--:--Cu----").collapse(),
+            shape("CuCuCu--:--Cu----")
+        );
+        let stable = shape("CuCuCuCu:P-P-P-P-:RuRuRuRu");
+        assert_eq!(stable.collapse(), stable);
+    }
+
+    #[test]
+    fn test_crystals_stick_vertically() {
+        let groups = shape("cr------:crcr----").groups();
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].supported);
+    }
+
+    #[test]
+    fn test_falling_crystals_shatter() {
+        assert_eq!(shape("Cu------:--cr----").collapse(), shape("Cu------"));
+        assert_eq!(shape("Cu------:--crCu--").collapse(), shape("Cu--Cu--"));
+    }
+
+    #[test]
+    fn test_shatter_cut_crystals() {
+        let mut layers = shape("CucrcrCu:--------:cr----cr").items;
+        shatter_cut_crystals(&mut layers);
+        assert_eq!(Shape { items: layers }, shape("Cu----Cu:--------:--------"));
+
+        let mut layers = shape("crcr----:cr----Cu").items;
+        shatter_cut_crystals(&mut layers);
+        assert_eq!(Shape { items: layers }, shape("crcr----:cr----Cu"));
+    }
+
+    #[test]
+    fn test_collapse_hex() {
+        let hex = HexShape::try_from_string("Cu----------:--CuCu------:----Cu------").unwrap();
+        assert_eq!(
+            hex.collapse(),
+            HexShape::try_from_string("CuCuCu------:----Cu------").unwrap()
+        );
+    }
+}
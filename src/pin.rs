@@ -0,0 +1,78 @@
+use crate::{
+    physics,
+    shape::{EColor, EShape, GenericLayer, GenericShape, SingleItem},
+};
+
+/// The pin pusher lifts a shape by one layer and puts a pin under every
+/// non-empty quadrant of its bottom layer. The top layer is dropped when the
+/// shape gets higher than its layer limit.
+pub trait PinPushable: Sized + Copy {
+    fn pin_pushed(&mut self);
+    fn pin_push(&self) -> Self {
+        let mut shape = *self;
+        shape.pin_pushed();
+        shape
+    }
+}
+
+impl<const N: usize, const L: usize> PinPushable for GenericShape<N, L> {
+    fn pin_pushed(&mut self) {
+        let height = self.layer_height();
+        if height == 0 {
+            return;
+        }
+
+        let mut pins = GenericLayer::<N>::default();
+        for j in 0..N {
+            if self[0][j].shape != EShape::Empty {
+                pins[j] = SingleItem {
+                    shape: EShape::Pin,
+                    color: EColor::Empty,
+                };
+            }
+        }
+
+        let mut layers = vec![pins];
+        layers.extend_from_slice(&self.items[..height]);
+        layers.truncate(L);
+        physics::collapse(&mut layers);
+
+        *self = GenericShape::default();
+        for (i, layer) in layers.into_iter().enumerate() {
+            self[i] = layer;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{shape, Shape};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_pin_push() {
+        assert_eq!(shape("CuCu----").pin_push(), shape("P-P-----:CuCu----"));
+        assert_eq!(
+            shape("Cu--Ru--:RuRuRuRu").pin_push(),
+            shape("P---P---:Cu--Ru--:RuRuRuRu")
+        );
+        assert_eq!(Shape::default().pin_push(), Shape::default());
+    }
+
+    #[test]
+    fn test_pin_push_drops_top_layer() {
+        let full = shape("CuCuCuCu:RuRuRuRu:SuSuSuSu:WuWuWuWu");
+        assert_eq!(
+            full.pin_push(),
+            shape("P-P-P-P-:CuCuCuCu:RuRuRuRu:SuSuSuSu")
+        );
+    }
+
+    #[test]
+    fn test_pin_push_collapses() {
+        // parts that were floating fall once the shape is pushed up
+        let ori = shape("Cu------:--Cu----");
+        assert_eq!(ori.pin_push(), shape("P-------:CuCu----"));
+    }
+}
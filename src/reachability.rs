@@ -0,0 +1,318 @@
+use crate::{
+    packed::LayerId,
+    shape::{EColor, SingleLayer},
+    solver::{Operation, RAW_SHAPES},
+};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"SZ2R";
+const VERSION: u8 = 1;
+const NO_PARENT: u32 = u32::MAX;
+
+/// Colors a raw shape can be painted in before it enters the layer search
+pub const PAINT_COLORS: [EColor; 8] = [
+    EColor::Uncolored,
+    EColor::Red,
+    EColor::Green,
+    EColor::Blue,
+    EColor::Yellow,
+    EColor::Magenta,
+    EColor::Cyan,
+    EColor::White,
+];
+
+/// How a layer was first reached: the layer it came from and the index of
+/// the operation in `Operation::successors` of that layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    parent: u32,
+    successor: u16,
+}
+
+/// Cache of layer search results: every single layer that can be built from
+/// a set of start layers by rotating, cutting and swapping with the start
+/// layers, with a recipe using the fewest operations for each.
+///
+/// This is only what one layer search can reach, not everything the planner
+/// can build. A layer missing from the cache may still come out of
+/// `Solver::solve`, which also swaps together halves made by separate
+/// searches and so reaches layers such as `CrWbRgSy`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SearchCache {
+    start_shapes: Vec<SingleLayer>,
+    entries: HashMap<LayerId, Entry>,
+}
+
+impl Debug for SearchCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SearchCache")
+            .field("start_shapes", &self.start_shapes)
+            .field("layers", &self.entries.len())
+            .finish()
+    }
+}
+
+impl SearchCache {
+    /// The raw shapes in every paint color, full and single layered
+    pub fn standard_start_shapes() -> Vec<SingleLayer> {
+        RAW_SHAPES
+            .iter()
+            .flat_map(|&shape| {
+                PAINT_COLORS
+                    .iter()
+                    .map(move |&color| SingleLayer::new_with_shape_color(shape, color))
+            })
+            .collect()
+    }
+
+    /// Breadth-first search over everything reachable from `start_shapes`.
+    ///
+    /// Panics with so many start shapes that a layer has more than
+    /// `u16::MAX` successors.
+    pub fn build(start_shapes: Vec<SingleLayer>) -> SearchCache {
+        let mut entries = HashMap::new();
+        let mut queue = VecDeque::new();
+        for layer in start_shapes.iter() {
+            let id = LayerId::from(*layer);
+            entries.entry(id).or_insert_with(|| {
+                queue.push_back(id);
+                Entry {
+                    parent: NO_PARENT,
+                    successor: 0,
+                }
+            });
+        }
+
+        while let Some(id) = queue.pop_front() {
+            let layer = SingleLayer::from(id);
+            for (i, operation) in Operation::successors(&layer, &start_shapes)
+                .into_iter()
+                .enumerate()
+            {
+                let next = LayerId::from(operation.output());
+                entries.entry(next).or_insert_with(|| {
+                    queue.push_back(next);
+                    Entry {
+                        parent: id.bits(),
+                        successor: u16::try_from(i).expect("too many start shapes"),
+                    }
+                });
+            }
+        }
+
+        SearchCache {
+            start_shapes,
+            entries,
+        }
+    }
+
+    pub fn start_shapes(&self) -> &[SingleLayer] {
+        &self.start_shapes
+    }
+
+    /// Number of reachable layers, the start shapes included
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, layer: &SingleLayer) -> bool {
+        self.entries.contains_key(&LayerId::from(*layer))
+    }
+
+    /// Start shape and operations of a shortest recipe for `layer`, `None` if
+    /// it isn't in the cache. A cache read from a damaged file can have
+    /// links that lead nowhere or go round in circles, which is an error.
+    pub fn recipe(&self, layer: &SingleLayer) -> io::Result<Option<(SingleLayer, Vec<Operation>)>> {
+        let corrupt = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut id = LayerId::from(*layer);
+        let Some(mut entry) = self.entries.get(&id).copied() else {
+            return Ok(None);
+        };
+        let mut operations = Vec::new();
+        while entry.parent != NO_PARENT {
+            // a shortest recipe never visits a layer twice
+            if operations.len() >= self.entries.len() {
+                return Err(corrupt("recipe goes round in circles"));
+            }
+            let parent =
+                LayerId::from_bits(entry.parent).ok_or_else(|| corrupt("invalid parent layer"))?;
+            let from = SingleLayer::from(parent);
+            let successors = Operation::successors(&from, &self.start_shapes);
+            let operation = successors
+                .get(entry.successor as usize)
+                .ok_or_else(|| corrupt("invalid operation"))?;
+            operations.push(*operation);
+            id = parent;
+            entry = *self
+                .entries
+                .get(&id)
+                .ok_or_else(|| corrupt("parent layer missing"))?;
+        }
+        operations.reverse();
+        Ok(Some((SingleLayer::from(id), operations)))
+    }
+
+    /// Write the cache in the compact binary format read by `read_from`:
+    /// a header with the start shapes, then 10 bytes per layer
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&(self.start_shapes.len() as u32).to_le_bytes())?;
+        for layer in self.start_shapes.iter() {
+            writer.write_all(&LayerId::from(*layer).bits().to_le_bytes())?;
+        }
+        let mut ids: Vec<&LayerId> = self.entries.keys().collect();
+        ids.sort();
+        writer.write_all(&(ids.len() as u32).to_le_bytes())?;
+        for id in ids {
+            let entry = self.entries[id];
+            writer.write_all(&id.bits().to_le_bytes())?;
+            writer.write_all(&entry.parent.to_le_bytes())?;
+            writer.write_all(&entry.successor.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read a cache written by `write_to`. The counts in the file are
+    /// checked against its length before anything is allocated for them.
+    pub fn read_from(mut reader: impl Read) -> io::Result<SearchCache> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut reader = bytes.as_slice();
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(invalid("not a search cache"));
+        }
+
+        let start_count = read_u32(&mut reader)? as usize;
+        if start_count > reader.len() / 4 {
+            return Err(invalid("more start shapes than the file holds"));
+        }
+        let mut start_shapes = Vec::with_capacity(start_count);
+        for _ in 0..start_count {
+            let id = LayerId::from_bits(read_u32(&mut reader)?)
+                .ok_or_else(|| invalid("invalid start shape"))?;
+            start_shapes.push(SingleLayer::from(id));
+        }
+
+        let count = read_u32(&mut reader)? as usize;
+        if count.checked_mul(10) != Some(reader.len()) {
+            return Err(invalid("layer count doesn't match the file length"));
+        }
+        let mut entries = HashMap::with_capacity(count);
+        for _ in 0..count {
+            let id = LayerId::from_bits(read_u32(&mut reader)?)
+                .ok_or_else(|| invalid("invalid layer"))?;
+            let parent = read_u32(&mut reader)?;
+            let mut successor = [0; 2];
+            reader.read_exact(&mut successor)?;
+            entries.insert(
+                id,
+                Entry {
+                    parent,
+                    successor: u16::from_le_bytes(successor),
+                },
+            );
+        }
+
+        Ok(SearchCache {
+            start_shapes,
+            entries,
+        })
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::layer;
+    use pretty_assertions::assert_eq;
+
+    fn small_cache() -> SearchCache {
+        SearchCache::build(vec![layer("CuCuCuCu"), layer("RrRrRrRr")])
+    }
+
+    #[test]
+    fn test_build() {
+        let cache = small_cache();
+        // out of the 81 layers of circles, rectangles and empty parts
+        assert_eq!(cache.len(), 63);
+        assert!(cache.contains(&layer("Cu--RrCu")));
+        assert!(!cache.contains(&layer("Sb------")));
+
+        let (start, operations) = cache.recipe(&layer("CuCuRrRr")).unwrap().unwrap();
+        assert_eq!(start, layer("CuCuCuCu"));
+        assert_eq!(
+            operations,
+            vec![Operation::Swap {
+                east: layer("CuCuCuCu"),
+                west: layer("RrRrRrRr"),
+                output: layer("CuCuRrRr")
+            }]
+        );
+        assert_eq!(
+            cache.recipe(&layer("RrRrRrRr")).unwrap(),
+            Some((layer("RrRrRrRr"), vec![]))
+        );
+        assert_eq!(cache.recipe(&layer("Sb------")).unwrap(), None);
+        assert_eq!(SearchCache::standard_start_shapes().len(), 32);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let cache = small_cache();
+        let mut bytes = Vec::new();
+        cache.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 5 + 4 + 2 * 4 + 4 + cache.len() * 10);
+        assert_eq!(SearchCache::read_from(bytes.as_slice()).unwrap(), cache);
+
+        bytes[0] = b'X';
+        assert!(SearchCache::read_from(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_corrupt() {
+        // a huge layer count in a file with no room for it
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(u32::MAX.to_le_bytes());
+        assert_eq!(bytes.len(), 13);
+        assert!(SearchCache::read_from(bytes.as_slice()).is_err());
+
+        let mut bytes = Vec::new();
+        small_cache().write_to(&mut bytes).unwrap();
+        for length in [5, 9, 16, bytes.len() - 1] {
+            assert!(SearchCache::read_from(&bytes[..length]).is_err());
+        }
+        bytes.push(0);
+        assert!(SearchCache::read_from(bytes.as_slice()).is_err());
+
+        // a start shape made by a layer made from it
+        let mut cache = small_cache();
+        let swapped = LayerId::from(layer("CuCuRrRr"));
+        cache.entries.insert(
+            LayerId::from(layer("CuCuCuCu")),
+            Entry {
+                parent: swapped.bits(),
+                successor: 0,
+            },
+        );
+        assert!(cache.recipe(&layer("CuCuRrRr")).is_err());
+        assert!(cache.recipe(&layer("RrRrRrRr")).unwrap().is_some());
+    }
+}
@@ -0,0 +1,302 @@
+use crate::shape::{EColor, EShape, GenericShape, SingleItem, SHAPEZ2_DEMENTION};
+use std::{f64::consts::PI, fmt::Write, io::IsTerminal};
+
+const RESET: &str = "\x1b[0m";
+
+/// Color the game draws `color` with, `None` for empty parts
+pub fn color_rgb(color: EColor) -> Option<(u8, u8, u8)> {
+    match color {
+        EColor::Red => Some((0xff, 0x66, 0x6a)),
+        EColor::Green => Some((0x78, 0xff, 0x66)),
+        EColor::Blue => Some((0x66, 0xa7, 0xff)),
+        EColor::Yellow => Some((0xfc, 0xf5, 0x2a)),
+        EColor::Magenta => Some((0xdd, 0x66, 0xff)),
+        EColor::Cyan => Some((0x00, 0xfc, 0xff)),
+        EColor::White => Some((0xff, 0xff, 0xff)),
+        EColor::Black => Some((0x3a, 0x3a, 0x3a)),
+        EColor::Uncolored => Some((0xaa, 0xaa, 0xaa)),
+        EColor::Empty => None,
+    }
+}
+
+fn glyph(shape: EShape) -> char {
+    match shape {
+        EShape::Circle => '●',
+        EShape::Rectangle => '■',
+        EShape::Windmill => '◣',
+        EShape::Star => '★',
+        EShape::Pin => '▲',
+        EShape::Crystal => '◆',
+        EShape::Empty => '·',
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    /// glyphs in 24-bit colors, with box-drawing lines
+    Ansi,
+    /// the part codes only
+    Ascii,
+}
+
+impl Style {
+    fn cell(self, item: SingleItem) -> String {
+        match self {
+            Style::Ansi => match (item.shape, color_rgb(item.color)) {
+                // pins and empty parts have no color of their own
+                (EShape::Pin | EShape::Empty, _) | (_, None) => {
+                    format!(" \x1b[2m{}{} ", glyph(item.shape), RESET)
+                }
+                (shape, Some((r, g, b))) => {
+                    format!(" \x1b[38;2;{};{};{}m{}{} ", r, g, b, glyph(shape), RESET)
+                }
+            },
+            Style::Ascii => format!(" {} ", item),
+        }
+    }
+
+    fn width(self) -> usize {
+        match self {
+            Style::Ansi => 3,
+            Style::Ascii => 4,
+        }
+    }
+
+    /// Vertical line, horizontal line and their crossing
+    fn lines(self) -> (char, char, char) {
+        match self {
+            Style::Ansi => ('│', '─', '┼'),
+            Style::Ascii => ('|', '-', '+'),
+        }
+    }
+
+    /// Every layer from the top down, a quad layer drawn as its four
+    /// quadrants seen from above and other layers as a row of parts
+    fn render<const N: usize, const L: usize>(self, shape: &GenericShape<N, L>) -> String {
+        let (vertical, horizontal, cross) = self.lines();
+        let mut lines = Vec::new();
+        for i in (0..shape.layer_height()).rev() {
+            lines.push(format!("layer {}", i));
+            let cells: Vec<String> = shape[i].items.iter().map(|&item| self.cell(item)).collect();
+            if N == SHAPEZ2_DEMENTION {
+                let line = horizontal.to_string().repeat(self.width());
+                lines.push(format!("{}{}{}", cells[3], vertical, cells[0]));
+                lines.push(format!("{}{}{}", line, cross, line));
+                lines.push(format!("{}{}{}", cells[2], vertical, cells[1]));
+            } else {
+                lines.push(cells.join(&vertical.to_string()));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// Quadrant diagrams of every layer in 24-bit ANSI colors
+pub fn render_ansi<const N: usize, const L: usize>(shape: &GenericShape<N, L>) -> String {
+    Style::Ansi.render(shape)
+}
+
+/// The same diagrams as `render_ansi` in plain ASCII, with part codes
+/// instead of colored glyphs
+pub fn render_ascii<const N: usize, const L: usize>(shape: &GenericShape<N, L>) -> String {
+    Style::Ascii.render(shape)
+}
+
+/// `render_ansi` when stdout is a terminal, `render_ascii` otherwise, so
+/// redirected output stays free of escape codes
+pub fn render_terminal<const N: usize, const L: usize>(shape: &GenericShape<N, L>) -> String {
+    if std::io::stdout().is_terminal() {
+        render_ansi(shape)
+    } else {
+        render_ascii(shape)
+    }
+}
+
+/// Radius of the bottom layer in the SVG drawings, which are 100 units wide
+const SVG_RADIUS: f64 = 40.0;
+const SVG_OUTLINE: &str = "#555555";
+const SVG_PIN: &str = "#404040";
+
+fn polar(angle: f64, radius: f64) -> (f64, f64) {
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+fn svg_point(angle: f64, radius: f64) -> String {
+    let (x, y) = polar(angle, radius);
+    format!("{:.2} {:.2}", x, y)
+}
+
+fn svg_rgb(color: EColor) -> String {
+    let (r, g, b) = color_rgb(color).unwrap_or((0xaa, 0xaa, 0xaa));
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// SVG element drawing `item` in the part from `start` to `end`, angles in
+/// radians clockwise from east, `None` for empty parts
+fn svg_part(item: SingleItem, start: f64, end: f64, radius: f64, corner: f64) -> Option<String> {
+    let middle = (start + end) / 2.0;
+    let fill = svg_rgb(item.color);
+    let polygon = |points: &[(f64, f64)], extra: &str| {
+        let points: Vec<String> = points
+            .iter()
+            .map(|&(angle, radius)| svg_point(angle, radius))
+            .collect();
+        format!(
+            "<path d=\"M0 0L{}Z\" fill=\"{}\" stroke=\"{}\"{}/>",
+            points.join("L"),
+            fill,
+            SVG_OUTLINE,
+            extra
+        )
+    };
+    match item.shape {
+        EShape::Empty => None,
+        EShape::Circle => Some(format!(
+            "<path d=\"M0 0L{}A{:.2} {:.2} 0 0 1 {}Z\" fill=\"{}\" stroke=\"{}\"/>",
+            svg_point(start, radius),
+            radius,
+            radius,
+            svg_point(end, radius),
+            fill,
+            SVG_OUTLINE
+        )),
+        EShape::Rectangle => Some(polygon(
+            &[(start, radius), (middle, corner), (end, radius)],
+            "",
+        )),
+        EShape::Windmill => Some(polygon(
+            &[(start, radius * 0.6), (middle, corner), (end, radius)],
+            "",
+        )),
+        EShape::Star => Some(polygon(
+            &[
+                (start, radius * 0.6),
+                (middle, radius * 1.2),
+                (end, radius * 0.6),
+            ],
+            "",
+        )),
+        EShape::Crystal => Some(polygon(
+            &[(start, radius), (middle, corner), (end, radius)],
+            " fill-opacity=\"0.75\"",
+        )),
+        EShape::Pin => {
+            let (x, y) = polar(middle, radius * 0.55);
+            Some(format!(
+                "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" fill=\"{}\"/>",
+                x,
+                y,
+                radius * 0.15,
+                SVG_PIN
+            ))
+        }
+    }
+}
+
+/// Standalone SVG image of `shape` seen from above, like the shape viewer:
+/// every layer is drawn a bit smaller on top of the one under it, each part
+/// filling its sector with the game's palette
+pub fn render_svg<const N: usize, const L: usize>(shape: &GenericShape<N, L>) -> String {
+    let mut svg = String::from(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-50 -50 100 100\" \
+         width=\"200\" height=\"200\">",
+    );
+    svg.push_str("<circle r=\"48\" fill=\"#2b2f3a\"/>");
+    let sector = 2.0 * PI / N as f64;
+    for i in 0..shape.layer_height() {
+        // evenly smaller up to the top layer, whatever the number of layers
+        let radius = SVG_RADIUS * (1.0 - i as f64 / (L as f64 + 1.0));
+        // a straight-edged part reaches the corner between its two edges
+        let corner = radius / (sector / 2.0).cos();
+        svg.push_str("<g stroke-width=\"1\" stroke-linejoin=\"round\">");
+        for (j, &item) in shape[i].items.iter().enumerate() {
+            // part 0 starts at north, the parts go on clockwise
+            let start = -PI / 2.0 + sector * j as f64;
+            if let Some(part) = svg_part(item, start, start + sector, radius, corner) {
+                let _ = write!(svg, "{}", part);
+            }
+        }
+        svg.push_str("</g>");
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{GenericShape, HexShape, Shape};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_render_ascii() {
+        let shape = Shape::try_from_string("CrP-Sb--:Cu------").unwrap();
+        assert_eq!(
+            render_ascii(&shape),
+            "layer 1\n \
+             -- | Cu \n\
+             ----+----\n \
+             -- | -- \n\
+             layer 0\n \
+             -- | Cr \n\
+             ----+----\n \
+             Sb | P- "
+        );
+        assert_eq!(render_ascii(&Shape::default()), "");
+
+        let hex = HexShape::try_from_string("CuRuSuWuP-cr").unwrap();
+        assert_eq!(render_ascii(&hex), "layer 0\n Cu | Ru | Su | Wu | P- | cr ");
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let shape = Shape::try_from_string("Cr------").unwrap();
+        assert_eq!(
+            render_ansi(&shape),
+            "layer 0\n \
+             \x1b[2m·\x1b[0m │ \x1b[38;2;255;102;106m●\x1b[0m \n\
+             ───┼───\n \
+             \x1b[2m·\x1b[0m │ \x1b[2m·\x1b[0m "
+        );
+        assert_eq!(render_ansi(&Shape::default()), "");
+
+        // pins don't look like the line between the quadrants
+        let pins = render_ansi(&Shape::try_from_string("P-P-P-P-").unwrap());
+        assert_eq!(pins.matches('▲').count(), 4);
+        assert_eq!(pins.matches('│').count(), 2);
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let svg = render_svg(&Shape::try_from_string("CrRgWbSy:P-cm----").unwrap());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<g ").count(), 2);
+        assert_eq!(svg.matches("<path ").count(), 5);
+        // the background and the pin
+        assert_eq!(svg.matches("<circle ").count(), 2);
+        for color in ["#ff666a", "#78ff66", "#66a7ff", "#fcf52a", "#dd66ff"] {
+            assert!(svg.contains(color), "{}", color);
+        }
+        // the circle in the north east quadrant ends at the east edge
+        assert!(svg.contains("<path d=\"M0 0L0.00 -40.00A40.00 40.00 0 0 1 40.00 0.00Z\""));
+
+        let empty = render_svg(&Shape::default());
+        assert_eq!(empty.matches("<path ").count(), 0);
+        let hex = render_svg(&HexShape::try_from_string("CuCuCuCuCuCu").unwrap());
+        assert_eq!(hex.matches("<path ").count(), 6);
+
+        // every layer of a tall shape still has a radius, smaller than the
+        // one under it
+        let tall = GenericShape::<4, 8>::try_from_string(&["Cu------"; 8].join(":")).unwrap();
+        let svg = render_svg(&tall);
+        let radii: Vec<f64> = svg
+            .split("A")
+            .skip(1)
+            .map(|arc| arc.split(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(radii.len(), 8);
+        assert!(radii[7] > 0.0);
+        assert!(radii.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{error::EvalError, expr::Evaluator, shape::Shape};
+use std::fmt::Display;
+
+pub const HELP: &str = "Enter shape expressions, such as rot_cw(CuRu----), or assign them to
+names, such as x = cut_left(CuCuCuCu). Functions: rot_cw, rot_ccw, rot_180,
+cut, cut_left, cut_right, half_destroy, swap, stack, pin_push, collapse,
+paint(shape, color) and crystallize(shape, color).
+
+commands:
+  :list            show every named shape
+  :undo            take back the last line that changed a name
+  :redo            apply the last line taken back again
+  :history         show the lines applied so far
+  :export <file>   save the history as a script for `shapez2-calc eval`
+  :help            show this help
+  :quit            leave";
+
+/// What the workspace has to show after a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// the outputs of an expression
+    Shapes(Vec<Shape>),
+    /// named shapes, the ones a line assigned or the whole workspace
+    Variables(Vec<(String, Vec<Shape>)>),
+    Text(String),
+    Quit,
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    Eval(EvalError),
+    UnknownCommand(String),
+    NothingToUndo,
+    NothingToRedo,
+    Export(String, std::io::Error),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Eval(err) => write!(f, "{}", err),
+            CommandError::UnknownCommand(command) => {
+                write!(f, "unknown command {:?}, try :help", command)
+            }
+            CommandError::NothingToUndo => write!(f, "nothing to undo"),
+            CommandError::NothingToRedo => write!(f, "nothing to redo"),
+            CommandError::Export(path, err) => write!(f, "{}: {}", path, err),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// A line that changed the workspace, with the variables from before it
+#[derive(Debug, Clone)]
+struct Applied {
+    line: String,
+    before: Evaluator,
+}
+
+/// Named shapes built up line by line, with undo and redo.
+///
+/// Lines are expressions of the `expr` language and are applied all or
+/// nothing. Commands start with `:`.
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    evaluator: Evaluator,
+    /// lines applied so far, the last one first to be undone
+    applied: Vec<Applied>,
+    /// lines taken back, the last one first to be redone
+    undone: Vec<Applied>,
+}
+
+impl Workspace {
+    pub fn new() -> Workspace {
+        Workspace::default()
+    }
+
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.evaluator
+    }
+
+    /// Lines applied so far, oldest first
+    pub fn history(&self) -> impl Iterator<Item = &str> {
+        self.applied.iter().map(|applied| applied.line.as_str())
+    }
+
+    /// The history as a script that `eval` runs line by line
+    pub fn export(&self) -> String {
+        self.history().map(|line| format!("{}\n", line)).collect()
+    }
+
+    pub fn execute(&mut self, line: &str) -> Result<Reply, CommandError> {
+        let line = line.trim();
+        let Some(command) = line.strip_prefix(':') else {
+            return self.apply(line);
+        };
+        let (command, argument) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(command, argument)| {
+                (command, argument.trim())
+            });
+        match (command, argument) {
+            ("list", "") => Ok(Reply::Variables(
+                self.evaluator
+                    .variables()
+                    .map(|(name, value)| (name.to_string(), value.to_vec()))
+                    .collect(),
+            )),
+            ("undo", "") => {
+                let applied = self.applied.pop().ok_or(CommandError::NothingToUndo)?;
+                let after = std::mem::replace(&mut self.evaluator, applied.before);
+                let text = format!("undid {}", applied.line);
+                self.undone.push(Applied {
+                    line: applied.line,
+                    before: after,
+                });
+                Ok(Reply::Text(text))
+            }
+            ("redo", "") => {
+                let undone = self.undone.pop().ok_or(CommandError::NothingToRedo)?;
+                let before = std::mem::replace(&mut self.evaluator, undone.before);
+                let text = format!("redid {}", undone.line);
+                self.applied.push(Applied {
+                    line: undone.line,
+                    before,
+                });
+                Ok(Reply::Text(text))
+            }
+            ("history", "") => Ok(Reply::Text(
+                self.history()
+                    .enumerate()
+                    .map(|(i, line)| format!("{}. {}", i + 1, line))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )),
+            ("export", path) if !path.is_empty() => {
+                std::fs::write(path, self.export())
+                    .map_err(|err| CommandError::Export(path.to_string(), err))?;
+                Ok(Reply::Text(format!(
+                    "wrote {} lines to {}",
+                    self.applied.len(),
+                    path
+                )))
+            }
+            ("help", "") => Ok(Reply::Text(HELP.to_string())),
+            ("quit" | "q" | "exit", "") => Ok(Reply::Quit),
+            _ => Err(CommandError::UnknownCommand(line.to_string())),
+        }
+    }
+
+    /// Evaluate `line` on a copy of the variables and keep the copy only if
+    /// every statement succeeds. Only lines that change a variable go into
+    /// the history, so there is something to undo for each of them.
+    fn apply(&mut self, line: &str) -> Result<Reply, CommandError> {
+        if line.is_empty() {
+            return Ok(Reply::Text(String::new()));
+        }
+        let mut evaluator = self.evaluator.clone();
+        let outputs = evaluator.eval(line).map_err(CommandError::Eval)?;
+        let changed: Vec<(String, Vec<Shape>)> = evaluator
+            .variables()
+            .filter(|&(name, value)| self.evaluator.variable(name) != Some(value))
+            .map(|(name, value)| (name.to_string(), value.to_vec()))
+            .collect();
+        if !changed.is_empty() {
+            let before = std::mem::replace(&mut self.evaluator, evaluator);
+            self.applied.push(Applied {
+                line: line.to_string(),
+                before,
+            });
+            self.undone.clear();
+        }
+        if outputs.is_empty() {
+            Ok(Reply::Variables(changed))
+        } else {
+            Ok(Reply::Shapes(outputs))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::shape;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_execute() {
+        let mut workspace = Workspace::new();
+        assert_eq!(
+            workspace.execute("x = CuRu----").unwrap(),
+            Reply::Variables(vec![("x".to_string(), vec![shape("CuRu----")])])
+        );
+        assert_eq!(
+            workspace.execute("rot_cw(x)").unwrap(),
+            Reply::Shapes(vec![shape("--CuRu--")])
+        );
+        assert!(matches!(
+            workspace.execute("y = cut(x); z = nope(x)"),
+            Err(CommandError::Eval(_))
+        ));
+        // the failed line didn't assign y, and rot_cw(x) assigned nothing
+        assert_eq!(workspace.evaluator().variable("y"), None);
+        assert_eq!(workspace.history().collect::<Vec<_>>(), ["x = CuRu----"]);
+        assert!(matches!(
+            workspace.execute(":frobnicate"),
+            Err(CommandError::UnknownCommand(_))
+        ));
+        assert_eq!(workspace.execute(":quit").unwrap(), Reply::Quit);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut workspace = Workspace::new();
+        workspace.execute("x = CuCuCuCu").unwrap();
+        workspace.execute("x = paint(x, r)").unwrap();
+        assert_eq!(
+            workspace.evaluator().variable("x"),
+            Some(&[shape("CrCrCrCr")][..])
+        );
+
+        // lines that change nothing aren't undone
+        workspace.execute("rot_cw(x)").unwrap();
+        workspace.execute("x = x").unwrap();
+        assert_eq!(
+            workspace.execute(":undo").unwrap(),
+            Reply::Text("undid x = paint(x, r)".to_string())
+        );
+        assert_eq!(
+            workspace.evaluator().variable("x"),
+            Some(&[shape("CuCuCuCu")][..])
+        );
+        // nor do they drop what could be redone
+        workspace.execute("cut(x)").unwrap();
+        workspace.execute(":redo").unwrap();
+        assert_eq!(
+            workspace.evaluator().variable("x"),
+            Some(&[shape("CrCrCrCr")][..])
+        );
+        assert!(matches!(
+            workspace.execute(":redo"),
+            Err(CommandError::NothingToRedo)
+        ));
+
+        workspace.execute(":undo").unwrap();
+        workspace.execute(":undo").unwrap();
+        assert_eq!(workspace.evaluator().variable("x"), None);
+        assert!(matches!(
+            workspace.execute(":undo"),
+            Err(CommandError::NothingToUndo)
+        ));
+
+        // a new line drops what could be redone
+        workspace.execute(":redo").unwrap();
+        workspace.execute("y = Ru").unwrap();
+        assert!(matches!(
+            workspace.execute(":redo"),
+            Err(CommandError::NothingToRedo)
+        ));
+    }
+
+    #[test]
+    fn test_export() {
+        let mut workspace = Workspace::new();
+        workspace.execute("x = CuCuCuCu").unwrap();
+        workspace.execute("west, east = cut(x)").unwrap();
+        workspace.execute("both = stack(east, west)").unwrap();
+        workspace.execute(":undo").unwrap();
+        let script = workspace.export();
+        assert_eq!(script, "x = CuCuCuCu\nwest, east = cut(x)\n");
+
+        let mut replay = Evaluator::new();
+        replay.eval(&script).unwrap();
+        assert_eq!(&replay, workspace.evaluator());
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{
+    cutting::Cuttable,
+    rotate::Rotatable,
+    shape::{GenericLayer, GenericShape, SingleItem, SHAPEZ2_DEMENTION},
+    stack::Stackable,
+};
+use std::fmt::Display;
+
+/// Set of layers, given part by part. `None` parts match any item.
+///
+/// Cutting and swapping throw half a layer away, so everything that could
+/// have been there is described by leaving that half open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GenericPattern<const N: usize> {
+    pub items: [Option<SingleItem>; N],
+}
+
+/// Pattern over the quad layers
+pub type LayerPattern = GenericPattern<SHAPEZ2_DEMENTION>;
+
+impl<const N: usize> Default for GenericPattern<N> {
+    fn default() -> Self {
+        GenericPattern { items: [None; N] }
+    }
+}
+
+impl<const N: usize> GenericPattern<N> {
+    /// Pattern matching `layer` only
+    pub fn exact(layer: &GenericLayer<N>) -> GenericPattern<N> {
+        GenericPattern {
+            items: layer.items.map(Some),
+        }
+    }
+
+    /// Pattern matching `layer` with the parts set in `mask` left open
+    pub fn masked(layer: &GenericLayer<N>, mask: usize) -> GenericPattern<N> {
+        let mut pattern = GenericPattern::exact(layer);
+        for j in 0..N {
+            if mask & (1 << j) != 0 {
+                pattern.items[j] = None;
+            }
+        }
+        pattern
+    }
+
+    pub fn matches(&self, layer: &GenericLayer<N>) -> bool {
+        (0..N).all(|j| self.items[j].is_none_or(|item| item == layer.items[j]))
+    }
+
+    /// Whether every part in `parts` may be empty
+    fn allows_empty(&self, parts: impl IntoIterator<Item = usize>) -> bool {
+        parts
+            .into_iter()
+            .all(|j| self.items[j].is_none_or(|item| item == SingleItem::default()))
+    }
+
+    /// Whether every part in `parts` may be the one of `layer`
+    fn allows_parts_of(
+        &self,
+        layer: &GenericLayer<N>,
+        parts: impl IntoIterator<Item = usize>,
+    ) -> bool {
+        parts
+            .into_iter()
+            .all(|j| self.items[j].is_none_or(|item| item == layer.items[j]))
+    }
+
+    /// Copy of the pattern with the parts in `parts` left open
+    fn opened(&self, parts: impl IntoIterator<Item = usize>) -> GenericPattern<N> {
+        let mut pattern = *self;
+        for j in parts {
+            pattern.items[j] = None;
+        }
+        pattern
+    }
+}
+
+fn east<const N: usize>() -> impl Iterator<Item = usize> {
+    (0..N).filter(|j| j * 2 / N == 0)
+}
+
+fn west<const N: usize>() -> impl Iterator<Item = usize> {
+    (0..N).filter(|j| j * 2 / N > 0)
+}
+
+impl<const N: usize> Rotatable for GenericPattern<N> {
+    const PARTS: usize = N;
+
+    fn rotate_once(&self) -> GenericPattern<N> {
+        let mut pattern = *self;
+        for i in 0..N {
+            pattern.items[(i + 1) % N] = self.items[i];
+        }
+        pattern
+    }
+
+    fn rotate_once_reverse(&self) -> GenericPattern<N> {
+        let mut pattern = *self;
+        for i in 0..N {
+            pattern.items[i] = self.items[(i + 1) % N];
+        }
+        pattern
+    }
+}
+
+impl<const N: usize> Display for GenericPattern<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for item in self.items.iter() {
+            match item {
+                Some(item) => write!(f, "{}", item)?,
+                None => write!(f, "??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait CuttableReverse<T: Cuttable>: Sized {
+    /// Every `x` with `x.half_destroy()` in `self`, `None` if there is none
+    fn half_destroy_preimage(&self) -> Option<Self>;
+    /// Every `x` whose halves from `cutting()` are in `self`, in the same
+    /// order as `cutting()`: keeping the west half, then the east half
+    fn cutting_preimages(&self) -> [Option<Self>; 2];
+    /// Every `x` that swaps with `other` into `self`: first the ones with
+    /// `swap(x, other)[0]` in `self`, then the ones with `swap(other, x)[0]`
+    /// in it
+    fn swap_preimages(&self, other: &T) -> [Option<Self>; 2];
+}
+
+/// Crystals stuck across the cut line are not taken into account, a layer
+/// found this way still has to be checked by running the operation
+impl<const N: usize> CuttableReverse<GenericLayer<N>> for GenericPattern<N> {
+    fn half_destroy_preimage(&self) -> Option<Self> {
+        self.allows_empty(west::<N>())
+            .then(|| self.opened(west::<N>()))
+    }
+
+    fn cutting_preimages(&self) -> [Option<Self>; 2] {
+        [
+            self.allows_empty(east::<N>())
+                .then(|| self.opened(east::<N>())),
+            self.half_destroy_preimage(),
+        ]
+    }
+
+    fn swap_preimages(&self, other: &GenericLayer<N>) -> [Option<Self>; 2] {
+        [
+            self.allows_parts_of(other, west::<N>())
+                .then(|| self.opened(west::<N>())),
+            self.allows_parts_of(other, east::<N>())
+                .then(|| self.opened(east::<N>())),
+        ]
+    }
+}
+
+pub trait StackableReverse: Stackable {
+    /// Every pair `(bottom, top)` of non-empty shapes that stacks into `self`
+    fn unstack(&self) -> Vec<(Self, Self)>;
+}
+
+/// Only splits between layers are considered, from the highest to the
+/// lowest, and each is checked by stacking it again
+impl<const N: usize, const L: usize> StackableReverse for GenericShape<N, L> {
+    fn unstack(&self) -> Vec<(Self, Self)> {
+        let height = self.layer_height();
+        let mut pairs = Vec::new();
+        for split in (1..height).rev() {
+            let mut bottom = GenericShape::default();
+            let mut top = GenericShape::default();
+            for i in 0..height {
+                if i < split {
+                    bottom[i] = self[i];
+                } else {
+                    top[i - split] = self[i];
+                }
+            }
+            if top.layer_height() > 0 && GenericShape::stack(&bottom, &top) == *self {
+                pairs.push((bottom, top));
+            }
+        }
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::{layer, Shape, SingleLayer};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_rotate_pattern() {
+        for _ in 0..100 {
+            let ori = rand::random::<SingleLayer>();
+            let pattern = LayerPattern::exact(&ori);
+            assert_eq!(
+                pattern.rotate_once(),
+                LayerPattern::exact(&ori.rotate_once())
+            );
+            assert_eq!(
+                pattern.rotate_once_reverse(),
+                LayerPattern::exact(&ori.rotate_once_reverse())
+            );
+            assert_eq!(pattern.rotate_once_reverse().rotate_once(), pattern);
+        }
+    }
+
+    #[test]
+    fn test_cut_preimages() {
+        let cut = LayerPattern::exact(&layer("CuRu----"));
+        let [keep_west, keep_east] = cut.cutting_preimages();
+        assert_eq!(keep_west, None);
+        let keep_east = keep_east.unwrap();
+        assert_eq!(keep_east.to_string(), "CuRu????");
+        for _ in 0..100 {
+            let ori = rand::random::<SingleLayer>();
+            for (i, half) in ori.cutting().iter().enumerate() {
+                let preimage = LayerPattern::exact(half).cutting_preimages()[i];
+                assert!(preimage.unwrap().matches(&ori));
+            }
+        }
+    }
+
+    #[test]
+    fn test_swap_preimages() {
+        let other = layer("SbSbSbSb");
+        let pattern = LayerPattern::exact(&layer("CuCuSbSb"));
+        let [first, second] = pattern.swap_preimages(&other);
+        assert_eq!(first.unwrap().to_string(), "CuCu????");
+        assert_eq!(second, None);
+        for _ in 0..100 {
+            let ori = rand::random::<SingleLayer>();
+            let [a, _] = SingleLayer::swap(&ori, &other);
+            assert!(LayerPattern::exact(&a).swap_preimages(&other)[0]
+                .unwrap()
+                .matches(&ori));
+            let [c, _] = SingleLayer::swap(&other, &ori);
+            assert!(LayerPattern::exact(&c).swap_preimages(&other)[1]
+                .unwrap()
+                .matches(&ori));
+        }
+    }
+
+    #[test]
+    fn test_unstack() {
+        let ori = Shape::try_from_string("CuCuCuCu:RuRu----:P-------").unwrap();
+        let pairs = ori.unstack();
+        assert_eq!(pairs.len(), 2);
+        for (bottom, top) in pairs {
+            assert_eq!(Shape::stack(&bottom, &top), ori);
+        }
+        assert_eq!(
+            LayerPattern::masked(&layer("CuCuCuCu"), 0b1100).to_string(),
+            "CuCu????"
+        );
+        let floating = Shape::try_from_string("Cu------:--Cu----").unwrap();
+        assert!(floating.unstack().is_empty());
+    }
+}
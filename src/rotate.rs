@@ -1,4 +1,4 @@
-use crate::shape::{Shape, SingleLayer, SHAPEZ2_DEMENTION, SHAPEZ2_LAYER};
+use crate::shape::{GenericLayer, GenericShape};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RotateDirection {
@@ -7,10 +7,13 @@ pub enum RotateDirection {
 }
 
 pub trait Rotatable: Sized + Copy {
+    /// number of parts around a layer, one rotation moves a part to the next one
+    const PARTS: usize;
+
     fn rotate_once(&self) -> Self;
     fn rotate_once_reverse(&self) -> Self;
     fn rotate_180(&self) -> Self {
-        self.rotate(RotateDirection::Clockwise, SHAPEZ2_DEMENTION / 2)
+        self.rotate(RotateDirection::Clockwise, Self::PARTS / 2)
     }
     fn rotated(&mut self, direction: RotateDirection, times: usize) {
         match direction {
@@ -33,39 +36,43 @@ pub trait Rotatable: Sized + Copy {
     }
 }
 
-impl Rotatable for Shape {
-    fn rotate_once(&self) -> Shape {
+impl<const N: usize, const L: usize> Rotatable for GenericShape<N, L> {
+    const PARTS: usize = N;
+
+    fn rotate_once(&self) -> GenericShape<N, L> {
         let mut shape = *self;
-        for i in 0..SHAPEZ2_LAYER {
+        for i in 0..L {
             shape[i] = shape[i].rotate_once();
         }
         shape
     }
 
-    fn rotate_once_reverse(&self) -> Shape {
+    fn rotate_once_reverse(&self) -> GenericShape<N, L> {
         let mut shape = *self;
-        for i in 0..SHAPEZ2_LAYER {
+        for i in 0..L {
             shape[i] = shape[i].rotate_once_reverse();
         }
         shape
     }
 }
 
-impl Rotatable for SingleLayer {
-    fn rotate_once(&self) -> SingleLayer {
+impl<const N: usize> Rotatable for GenericLayer<N> {
+    const PARTS: usize = N;
+
+    fn rotate_once(&self) -> GenericLayer<N> {
         let mut layer = *self;
         let ori_layer = *self;
-        for i in 0..SHAPEZ2_DEMENTION {
-            layer.items[SHAPEZ2_DEMENTION - 1 - i] = ori_layer.items[i];
+        for i in 0..N {
+            layer.items[(i + 1) % N] = ori_layer.items[i];
         }
         layer
     }
 
-    fn rotate_once_reverse(&self) -> SingleLayer {
+    fn rotate_once_reverse(&self) -> GenericLayer<N> {
         let mut layer = *self;
         let ori_layer = *self;
-        for i in 0..SHAPEZ2_DEMENTION {
-            layer.items[i] = ori_layer.items[SHAPEZ2_DEMENTION - 1 - i];
+        for i in 0..N {
+            layer.items[i] = ori_layer.items[(i + 1) % N];
         }
         layer
     }
@@ -74,6 +81,7 @@ impl Rotatable for SingleLayer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shape::{HexShape, Shape, SHAPEZ2_DEMENTION, SHAPEZ2_HEX_DEMENTION, SHAPEZ2_LAYER};
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -83,10 +91,26 @@ mod tests {
         for i in 0..SHAPEZ2_LAYER {
             for j in 0..SHAPEZ2_DEMENTION {
                 assert_eq!(
-                    new_shape.items[i][SHAPEZ2_DEMENTION - 1 - j],
-                    shape.items[j][i]
+                    new_shape.items[i][(j + 1) % SHAPEZ2_DEMENTION],
+                    shape.items[i][j]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotate_hex() {
+        let shape = HexShape::random();
+        let new_shape = shape.rotate_once();
+        for i in 0..SHAPEZ2_LAYER {
+            for j in 0..SHAPEZ2_HEX_DEMENTION {
+                assert_eq!(
+                    new_shape.items[i][(j + 1) % SHAPEZ2_HEX_DEMENTION],
+                    shape.items[i][j]
                 );
             }
         }
+        assert_eq!(shape.rotate_180().rotate_180(), shape);
+        assert_eq!(shape.rotate_once().rotate_once_reverse(), shape);
     }
 }
@@ -1,9 +1,11 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
+use crate::error::{ParseErrorKind, ShapeParseError};
 use derive_more::derive::{Index, IndexMut, IntoIterator};
 use rand::prelude::Distribution;
 
 pub const SHAPEZ2_DEMENTION: usize = 4;
+pub const SHAPEZ2_HEX_DEMENTION: usize = 6;
 pub const SHAPEZ2_LAYER: usize = 4;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -21,21 +23,97 @@ pub enum EColor {
 }
 
 impl EColor {
-    pub fn try_from_string(s: &str) -> Option<EColor> {
+    /// Every color, in declaration order
+    pub const ALL: [EColor; 10] = [
+        EColor::Red,
+        EColor::Green,
+        EColor::Blue,
+        EColor::Yellow,
+        EColor::Magenta,
+        EColor::Cyan,
+        EColor::White,
+        EColor::Black,
+        EColor::Uncolored,
+        EColor::Empty,
+    ];
+
+    pub fn try_from_string(s: &str) -> Result<EColor, ShapeParseError> {
         match s.to_lowercase().as_str() {
-            "r" => Some(EColor::Red),
-            "g" => Some(EColor::Green),
-            "b" => Some(EColor::Blue),
-            "y" => Some(EColor::Yellow),
-            "m" => Some(EColor::Magenta),
-            "c" => Some(EColor::Cyan),
-            "w" => Some(EColor::White),
-            "k" => Some(EColor::Black),
-            "u" => Some(EColor::Uncolored),
-            "-" => Some(EColor::Empty),
+            "r" => Ok(EColor::Red),
+            "g" => Ok(EColor::Green),
+            "b" => Ok(EColor::Blue),
+            "y" => Ok(EColor::Yellow),
+            "m" => Ok(EColor::Magenta),
+            "c" => Ok(EColor::Cyan),
+            "w" => Ok(EColor::White),
+            "k" => Ok(EColor::Black),
+            "u" => Ok(EColor::Uncolored),
+            "-" => Ok(EColor::Empty),
+            _ => Err(ShapeParseError::new(ParseErrorKind::InvalidColor(
+                s.to_string(),
+            ))),
+        }
+    }
+
+    /// Red, green and blue bits of a paint color, `None` for colors that
+    /// can't be mixed
+    fn rgb(&self) -> Option<u8> {
+        match self {
+            EColor::Red => Some(0b100),
+            EColor::Green => Some(0b010),
+            EColor::Blue => Some(0b001),
+            EColor::Yellow => Some(0b110),
+            EColor::Magenta => Some(0b101),
+            EColor::Cyan => Some(0b011),
+            EColor::White => Some(0b111),
             _ => None,
         }
     }
+
+    fn from_rgb(rgb: u8) -> Option<EColor> {
+        match rgb {
+            0b100 => Some(EColor::Red),
+            0b010 => Some(EColor::Green),
+            0b001 => Some(EColor::Blue),
+            0b110 => Some(EColor::Yellow),
+            0b101 => Some(EColor::Magenta),
+            0b011 => Some(EColor::Cyan),
+            0b111 => Some(EColor::White),
+            _ => None,
+        }
+    }
+
+    /// Result of the color mixer, e.g. red + green = yellow, or a primary and
+    /// the secondary missing it = white. Mixing a color with itself gives the
+    /// same color back; any other pair can't be mixed.
+    pub fn mix(a: EColor, b: EColor) -> Option<EColor> {
+        let (a_rgb, b_rgb) = (a.rgb()?, b.rgb()?);
+        if a_rgb == b_rgb {
+            return Some(a);
+        }
+        if a_rgb & b_rgb != 0 {
+            return None;
+        }
+        EColor::from_rgb(a_rgb | b_rgb)
+    }
+
+    /// Primary colors that have to be mixed to get this color, empty for
+    /// colors that aren't made from paint
+    pub fn primary_components(&self) -> Vec<EColor> {
+        let rgb = self.rgb().unwrap_or(0);
+        [EColor::Red, EColor::Green, EColor::Blue]
+            .into_iter()
+            .filter(|primary| primary.rgb().is_some_and(|bit| rgb & bit != 0))
+            .collect()
+    }
+}
+
+impl FromStr for EColor {
+    type Err = ShapeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EColor::try_from_string(s)
+    }
 }
 
 impl Display for EColor {
@@ -80,23 +158,50 @@ pub enum EShape {
     Windmill,
     Star,
     Pin,
+    Crystal,
     Empty,
 }
 
 impl EShape {
-    pub fn try_from_string(s: &str) -> Option<EShape> {
+    /// Every shape, in declaration order
+    pub const ALL: [EShape; 7] = [
+        EShape::Circle,
+        EShape::Rectangle,
+        EShape::Windmill,
+        EShape::Star,
+        EShape::Pin,
+        EShape::Crystal,
+        EShape::Empty,
+    ];
+
+    /// Shape codes are upper case, except for the crystal `c` which would
+    /// otherwise be mistaken for a circle
+    pub fn try_from_string(s: &str) -> Result<EShape, ShapeParseError> {
+        if s == "c" {
+            return Ok(EShape::Crystal);
+        }
         match s.to_uppercase().as_str() {
-            "C" => Some(EShape::Circle),
-            "R" => Some(EShape::Rectangle),
-            "W" => Some(EShape::Windmill),
-            "S" => Some(EShape::Star),
-            "P" => Some(EShape::Pin),
-            "-" => Some(EShape::Empty),
-            _ => None,
+            "C" => Ok(EShape::Circle),
+            "R" => Ok(EShape::Rectangle),
+            "W" => Ok(EShape::Windmill),
+            "S" => Ok(EShape::Star),
+            "P" => Ok(EShape::Pin),
+            "-" => Ok(EShape::Empty),
+            _ => Err(ShapeParseError::new(ParseErrorKind::InvalidShape(
+                s.to_string(),
+            ))),
         }
     }
 }
 
+impl FromStr for EShape {
+    type Err = ShapeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EShape::try_from_string(s)
+    }
+}
+
 impl Display for EShape {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
@@ -105,6 +210,7 @@ impl Display for EShape {
             EShape::Windmill => "W".to_string(),
             EShape::Star => "S".to_string(),
             EShape::Pin => "P".to_string(),
+            EShape::Crystal => "c".to_string(),
             EShape::Empty => "-".to_string(),
         };
         write!(f, "{}", s)
@@ -139,24 +245,53 @@ impl Default for SingleItem {
 }
 
 impl SingleItem {
+    /// Number of bits used by `to_bits`
+    pub const BITS: u32 = 7;
+
     pub fn new() -> SingleItem {
         Self::default()
     }
 
-    pub fn try_from_string(s: &str) -> Option<SingleItem> {
-        if s.len() != 2 {
-            return None;
-        }
-        let shape_code = &s[0..1];
-        let color_code = &s[1..2];
+    /// Pack the item into the low 7 bits, shape first then color
+    pub fn to_bits(&self) -> u8 {
+        self.shape as u8 | (self.color as u8) << 3
+    }
 
+    /// Unpack an item from `to_bits`, `None` for bits that don't name a
+    /// shape and a color
+    pub fn from_bits(bits: u8) -> Option<SingleItem> {
         Some(SingleItem {
-            shape: EShape::try_from_string(shape_code)?,
-            color: EColor::try_from_string(color_code)?,
+            shape: *EShape::ALL.get((bits & 0b111) as usize)?,
+            color: *EColor::ALL.get((bits >> 3) as usize)?,
+        })
+    }
+
+    pub fn try_from_string(s: &str) -> Result<SingleItem, ShapeParseError> {
+        let chars: Vec<char> = s.chars().collect();
+        if chars.len() != 2 {
+            return Err(ShapeParseError::new(ParseErrorKind::WrongLength {
+                expected: 2,
+                found: chars.len(),
+            }));
+        }
+        let shape_code = chars[0].to_string();
+        let color_code = chars[1].to_string();
+
+        Ok(SingleItem {
+            shape: EShape::try_from_string(&shape_code)?,
+            color: EColor::try_from_string(&color_code).map_err(|e| e.at_offset(1))?,
         })
     }
 }
 
+impl FromStr for SingleItem {
+    type Err = ShapeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SingleItem::try_from_string(s)
+    }
+}
+
 impl Display for SingleItem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match (self.shape, self.color) {
@@ -175,40 +310,52 @@ impl Distribution<SingleItem> for rand::distributions::Standard {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Index, IndexMut, IntoIterator, Default)]
-pub struct SingleLayer {
+/// A layer of `N` parts, numbered clockwise starting from the top right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Index, IndexMut, IntoIterator)]
+pub struct GenericLayer<const N: usize> {
     #[index]
     #[index_mut]
     #[into_iterator(owned, ref, ref_mut)]
-    pub items: [SingleItem; SHAPEZ2_DEMENTION],
+    pub items: [SingleItem; N],
+}
+
+/// Layer of the quad shapes
+pub type SingleLayer = GenericLayer<SHAPEZ2_DEMENTION>;
+/// Layer of the hexagonal shapes
+pub type HexLayer = GenericLayer<SHAPEZ2_HEX_DEMENTION>;
+
+impl<const N: usize> Default for GenericLayer<N> {
+    fn default() -> Self {
+        GenericLayer {
+            items: [SingleItem::default(); N],
+        }
+    }
 }
 
-impl SingleLayer {
-    pub fn new_with_shape(shape: EShape) -> SingleLayer {
+impl<const N: usize> GenericLayer<N> {
+    pub fn new_with_shape(shape: EShape) -> GenericLayer<N> {
         Self::new_with_shape_color(shape, EColor::Uncolored)
     }
 
-    pub fn new_with_shape_color(shape: EShape, color: EColor) -> SingleLayer {
+    pub fn new_with_shape_color(shape: EShape, color: EColor) -> GenericLayer<N> {
         let item = SingleItem { shape, color };
-        SingleLayer {
-            items: [item; SHAPEZ2_DEMENTION],
-        }
+        GenericLayer { items: [item; N] }
     }
 
-    pub fn try_from_string(s: &str) -> Option<SingleLayer> {
-        let mut layer = SingleLayer::default();
-        if s.len() != SHAPEZ2_DEMENTION * 2 {
-            return None;
+    pub fn try_from_string(s: &str) -> Result<GenericLayer<N>, ShapeParseError> {
+        let mut layer = GenericLayer::default();
+        let chars: Vec<char> = s.chars().collect();
+        if chars.len() != N * 2 {
+            return Err(ShapeParseError::new(ParseErrorKind::WrongLength {
+                expected: N * 2,
+                found: chars.len(),
+            }));
         }
-        for i in 0..SHAPEZ2_DEMENTION {
-            let code = &s[i * 2..i * 2 + 2];
-            if let Some(item) = SingleItem::try_from_string(code) {
-                layer.items[i] = item;
-            } else {
-                return None;
-            }
+        for i in 0..N {
+            let code: String = chars[i * 2..i * 2 + 2].iter().collect();
+            layer.items[i] = SingleItem::try_from_string(&code).map_err(|e| e.in_part(i, i * 2))?;
         }
-        Some(layer)
+        Ok(layer)
     }
 
     pub fn is_some(&self) -> bool {
@@ -216,7 +363,15 @@ impl SingleLayer {
     }
 }
 
-impl Display for SingleLayer {
+impl<const N: usize> FromStr for GenericLayer<N> {
+    type Err = ShapeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GenericLayer::try_from_string(s)
+    }
+}
+
+impl<const N: usize> Display for GenericLayer<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for item in self.items.iter() {
             write!(f, "{}", item)?;
@@ -225,11 +380,13 @@ impl Display for SingleLayer {
     }
 }
 
-impl Distribution<SingleLayer> for rand::distributions::Standard {
-    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> SingleLayer {
-        fn generate_single_layer<R: rand::Rng + ?Sized>(rng: &mut R) -> SingleLayer {
-            let mut items = [SingleItem::new(); SHAPEZ2_DEMENTION];
-            for item in items.iter_mut().take(SHAPEZ2_DEMENTION) {
+impl<const N: usize> Distribution<GenericLayer<N>> for rand::distributions::Standard {
+    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> GenericLayer<N> {
+        fn generate_single_layer<R: rand::Rng + ?Sized, const N: usize>(
+            rng: &mut R,
+        ) -> GenericLayer<N> {
+            let mut items = [SingleItem::new(); N];
+            for item in items.iter_mut().take(N) {
                 *item = match rand::random::<usize>() % 2 {
                     0 => SingleItem {
                         color: EColor::Empty,
@@ -250,7 +407,7 @@ impl Distribution<SingleLayer> for rand::distributions::Standard {
                     item.color = EColor::Empty;
                 }
             }
-            SingleLayer { items }
+            GenericLayer { items }
         }
         loop {
             let layer = generate_single_layer(rng);
@@ -261,32 +418,46 @@ impl Distribution<SingleLayer> for rand::distributions::Standard {
     }
 }
 
-/// Shape is a 4x4 matrix of SingleItem
+/// Shape is a L x N matrix of SingleItem, L being the maximum number of
+/// layers the scenario allows
 ///
 /// ```plaintext
-///  Layer 0
-///   3 | 0
-///   -----
-///   2 | 1
+///  Layer 0 (quad)     Layer 0 (hex)
+///   3 | 0               5 0
+///   -----             4  -  1
+///   2 | 1               3 2
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Index, IndexMut, IntoIterator, Default)]
-pub struct Shape {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Index, IndexMut, IntoIterator)]
+pub struct GenericShape<const N: usize, const L: usize = SHAPEZ2_LAYER> {
     #[index]
     #[index_mut]
     #[into_iterator(owned, ref, ref_mut)]
-    pub items: [SingleLayer; SHAPEZ2_LAYER],
+    pub items: [GenericLayer<N>; L],
 }
 
-impl Shape {
-    pub fn new_simple(shape: EShape, color: EColor) -> Shape {
-        let mut shape_s = Shape::default();
-        shape_s[0] = SingleLayer::new_with_shape_color(shape, color);
+/// Shape made of quadrants
+pub type Shape = GenericShape<SHAPEZ2_DEMENTION>;
+/// Shape made of six parts per layer
+pub type HexShape = GenericShape<SHAPEZ2_HEX_DEMENTION>;
+
+impl<const N: usize, const L: usize> Default for GenericShape<N, L> {
+    fn default() -> Self {
+        GenericShape {
+            items: [GenericLayer::default(); L],
+        }
+    }
+}
+
+impl<const N: usize, const L: usize> GenericShape<N, L> {
+    pub fn new_simple(shape: EShape, color: EColor) -> GenericShape<N, L> {
+        let mut shape_s = GenericShape::default();
+        shape_s[0] = GenericLayer::new_with_shape_color(shape, color);
         shape_s
     }
 
     pub fn layer_height(&self) -> usize {
         let mut height = 0;
-        for i in 0..SHAPEZ2_LAYER {
+        for i in 0..L {
             if self.items[i].is_some() {
                 height = i + 1;
             }
@@ -294,14 +465,14 @@ impl Shape {
         height
     }
 
-    pub fn random() -> Shape {
-        let shape_layer = rand::random::<usize>() % (SHAPEZ2_LAYER + 1);
+    pub fn random() -> GenericShape<N, L> {
+        let shape_layer = rand::random::<usize>() % (L + 1);
         Self::random_with_height(shape_layer)
     }
 
     /// start from 1
-    pub fn random_with_height(height: usize) -> Shape {
-        let mut shape = Shape::default();
+    pub fn random_with_height(height: usize) -> GenericShape<N, L> {
+        let mut shape = GenericShape::default();
         for i in 0..height {
             shape.items[i] = rand::random();
         }
@@ -310,12 +481,12 @@ impl Shape {
 
     pub fn to_minify_string(&self) -> String {
         let mut result = self.to_raw_string();
-        const EMPTY_LAYER: &str = ":--------";
+        let empty_layer = format!(":{}", GenericLayer::<N>::default());
         const EMPTY_ITEM: &str = "--";
         // search from right to left, when meet ":--------" remove it
         loop {
-            if result.ends_with(EMPTY_LAYER) {
-                result = result[0..result.len() - EMPTY_LAYER.len()].to_string();
+            if result.ends_with(&empty_layer) {
+                result = result[0..result.len() - empty_layer.len()].to_string();
             } else {
                 break;
             }
@@ -339,32 +510,47 @@ impl Shape {
 
     pub fn to_raw_string(&self) -> String {
         let mut result = String::new();
-        for i in 0..SHAPEZ2_LAYER {
-            result.push_str(&format!("{}", self.items[i]));
-            if i != SHAPEZ2_LAYER - 1 {
+        for i in 0..L {
+            if i != 0 {
                 result.push(':');
             }
+            result.push_str(&format!("{}", self.items[i]));
         }
         result
     }
 
-    pub fn try_from_string(s: &str) -> Option<Shape> {
-        let mut shape = Shape::default();
+    pub fn try_from_string(s: &str) -> Result<GenericShape<N, L>, ShapeParseError> {
+        let mut shape = GenericShape::default();
 
         let layer_strings: Vec<&str> = s.split(':').collect();
 
-        if layer_strings.len() > SHAPEZ2_LAYER {
-            return None;
+        if layer_strings.len() > L {
+            let offset = layer_strings[..L]
+                .iter()
+                .map(|l| l.chars().count() + 1)
+                .sum();
+            return Err(ShapeParseError::new(ParseErrorKind::TooManyLayers {
+                max: L,
+                found: layer_strings.len(),
+            })
+            .in_layer(L, offset));
+        }
+
+        // a single layer may have its trailing empty items minified away
+        if layer_strings.len() == 1 && s.chars().count() < N * 2 {
+            let layer_str = format!("{:-<width$}", s, width = N * 2);
+            shape.items[0] =
+                GenericLayer::try_from_string(&layer_str).map_err(|e| e.in_layer(0, 0))?;
+            return Ok(shape);
         }
 
+        let mut offset = 0;
         for (layer_index, layer_str) in layer_strings.iter().enumerate() {
-            if let Some(layer) = SingleLayer::try_from_string(layer_str) {
-                shape.items[layer_index] = layer;
-            } else {
-                return None;
-            }
+            shape.items[layer_index] = GenericLayer::try_from_string(layer_str)
+                .map_err(|e| e.in_layer(layer_index, offset))?;
+            offset += layer_str.chars().count() + 1;
         }
-        Some(shape)
+        Ok(shape)
     }
 
     pub fn to_shapez2_shape_viewer(&self) -> String {
@@ -389,17 +575,50 @@ impl Shape {
     }
 }
 
-impl Display for Shape {
+impl<const N: usize, const L: usize> FromStr for GenericShape<N, L> {
+    type Err = ShapeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GenericShape::try_from_string(s)
+    }
+}
+
+impl<const N: usize, const L: usize> Display for GenericShape<N, L> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.to_minify_string())
     }
 }
 
+/// Shape from its code, for tests
+#[cfg(test)]
+pub(crate) fn shape(s: &str) -> Shape {
+    Shape::try_from_string(s).unwrap()
+}
+
+/// Single layer from its code, for tests
+#[cfg(test)]
+pub(crate) fn layer(s: &str) -> SingleLayer {
+    SingleLayer::try_from_string(s).unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_item_bits() {
+        for shape in EShape::ALL {
+            for color in EColor::ALL {
+                let item = SingleItem { shape, color };
+                assert!(item.to_bits() < 1 << SingleItem::BITS);
+                assert_eq!(SingleItem::from_bits(item.to_bits()), Some(item));
+            }
+        }
+        assert_eq!(SingleItem::from_bits(0b111), None);
+        assert_eq!(SingleItem::from_bits(0b1111111), None);
+    }
+
     #[test]
     fn test_color_to_string() {
         assert_eq!(EColor::Red.to_string(), "r");
@@ -415,16 +634,72 @@ mod tests {
 
     #[test]
     fn test_color_try_from_string() {
-        assert_eq!(EColor::try_from_string("r"), Some(EColor::Red));
-        assert_eq!(EColor::try_from_string("g"), Some(EColor::Green));
-        assert_eq!(EColor::try_from_string("b"), Some(EColor::Blue));
-        assert_eq!(EColor::try_from_string("y"), Some(EColor::Yellow));
-        assert_eq!(EColor::try_from_string("m"), Some(EColor::Magenta));
-        assert_eq!(EColor::try_from_string("c"), Some(EColor::Cyan));
-        assert_eq!(EColor::try_from_string("w"), Some(EColor::White));
-        assert_eq!(EColor::try_from_string("u"), Some(EColor::Uncolored));
-        assert_eq!(EColor::try_from_string("-"), Some(EColor::Empty));
-        assert_eq!(EColor::try_from_string("x"), None);
+        assert_eq!(EColor::try_from_string("r"), Ok(EColor::Red));
+        assert_eq!(EColor::try_from_string("g"), Ok(EColor::Green));
+        assert_eq!(EColor::try_from_string("b"), Ok(EColor::Blue));
+        assert_eq!(EColor::try_from_string("y"), Ok(EColor::Yellow));
+        assert_eq!(EColor::try_from_string("m"), Ok(EColor::Magenta));
+        assert_eq!(EColor::try_from_string("c"), Ok(EColor::Cyan));
+        assert_eq!(EColor::try_from_string("w"), Ok(EColor::White));
+        assert_eq!(EColor::try_from_string("u"), Ok(EColor::Uncolored));
+        assert_eq!(EColor::try_from_string("-"), Ok(EColor::Empty));
+        assert!(EColor::try_from_string("x").is_err());
+    }
+
+    #[test]
+    fn test_color_mix() {
+        assert_eq!(
+            EColor::mix(EColor::Red, EColor::Green),
+            Some(EColor::Yellow)
+        );
+        assert_eq!(
+            EColor::mix(EColor::Blue, EColor::Red),
+            Some(EColor::Magenta)
+        );
+        assert_eq!(EColor::mix(EColor::Green, EColor::Blue), Some(EColor::Cyan));
+        assert_eq!(EColor::mix(EColor::Red, EColor::Cyan), Some(EColor::White));
+        assert_eq!(
+            EColor::mix(EColor::Yellow, EColor::Blue),
+            Some(EColor::White)
+        );
+        assert_eq!(EColor::mix(EColor::Red, EColor::Red), Some(EColor::Red));
+        assert_eq!(EColor::mix(EColor::Red, EColor::Yellow), None);
+        assert_eq!(EColor::mix(EColor::White, EColor::Red), None);
+        assert_eq!(EColor::mix(EColor::Uncolored, EColor::Red), None);
+    }
+
+    #[test]
+    fn test_color_primary_components() {
+        assert_eq!(EColor::Red.primary_components(), vec![EColor::Red]);
+        assert_eq!(
+            EColor::Yellow.primary_components(),
+            vec![EColor::Red, EColor::Green]
+        );
+        assert_eq!(
+            EColor::White.primary_components(),
+            vec![EColor::Red, EColor::Green, EColor::Blue]
+        );
+        assert_eq!(EColor::Uncolored.primary_components(), vec![]);
+    }
+
+    #[test]
+    fn test_crystal_and_cyan() {
+        assert_eq!(
+            SingleItem::try_from_string("cc"),
+            Ok(SingleItem {
+                shape: EShape::Crystal,
+                color: EColor::Cyan,
+            })
+        );
+        assert_eq!(
+            SingleItem::try_from_string("Cc"),
+            Ok(SingleItem {
+                shape: EShape::Circle,
+                color: EColor::Cyan,
+            })
+        );
+        let shape = Shape::try_from_string("CcccP-cr").unwrap();
+        assert_eq!(shape.to_minify_string(), "CcccP-cr");
     }
 
     #[test]
@@ -462,6 +737,8 @@ mod tests {
             shape.to_raw_string(),
             "Cr------:--------:--------:--------".to_string()
         );
+
+        assert_eq!(GenericShape::<4, 0>::default().to_raw_string(), "");
     }
 
     #[test]
@@ -498,7 +775,7 @@ mod tests {
 
         assert_eq!(
             Shape::try_from_string("CrRg----:CrRg----:CrRg----:CrRg----"),
-            Some(shape)
+            Ok(shape)
         );
     }
 
@@ -515,13 +792,98 @@ mod tests {
     fn test_ramdom_shape_loopback_raw_string() {
         let shape = Shape::random();
         let shape_str = shape.to_raw_string();
-        assert_eq!(Shape::try_from_string(&shape_str), Some(shape));
+        assert_eq!(Shape::try_from_string(&shape_str), Ok(shape));
     }
 
     #[test]
     fn test_ramdom_shape_loopback_minify_string() {
         let shape = Shape::random();
         let shape_str = shape.to_minify_string();
-        assert_eq!(Shape::try_from_string(&shape_str), Some(shape));
+        assert_eq!(Shape::try_from_string(&shape_str), Ok(shape));
+    }
+
+    #[test]
+    fn test_hex_shape_strings() {
+        let hex = HexShape::try_from_string("CuRuSuWuP-cr:Cu----------").unwrap();
+        assert_eq!(hex.to_minify_string(), "CuRuSuWuP-cr:Cu----------");
+        assert_eq!(hex.layer_height(), 2);
+        assert_eq!(
+            HexShape::try_from_string("Cu").unwrap().to_minify_string(),
+            "Cu"
+        );
+        assert!(Shape::try_from_string("CuRuSuWuP-cr").is_err());
+    }
+
+    #[test]
+    fn test_ramdom_hex_shape_loopback_minify_string() {
+        let shape = HexShape::random();
+        let shape_str = shape.to_minify_string();
+        assert_eq!(HexShape::try_from_string(&shape_str), Ok(shape));
+    }
+
+    #[test]
+    fn test_layer_limit() {
+        let five = "CuCuCuCu:RuRuRuRu:SuSuSuSu:WuWuWuWu:P-P-P-P-";
+        assert!(Shape::try_from_string(five).is_err());
+        let shape = GenericShape::<SHAPEZ2_DEMENTION, 5>::try_from_string(five).unwrap();
+        assert_eq!(shape.layer_height(), 5);
+        assert_eq!(shape.to_minify_string(), five);
+        assert_eq!(
+            GenericShape::<SHAPEZ2_DEMENTION, 5>::try_from_string("Cu------:Cu------")
+                .unwrap()
+                .to_raw_string(),
+            "Cu------:Cu------:--------:--------:--------"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Shape::try_from_string("CuCuCuCu:CuRxCuCu"),
+            Err(ShapeParseError {
+                layer: Some(1),
+                part: Some(1),
+                offset: 12,
+                kind: ParseErrorKind::InvalidColor("x".to_string()),
+            })
+        );
+        assert_eq!(
+            Shape::try_from_string("CuCuCuCu:CuCuXuCu").unwrap_err(),
+            ShapeParseError {
+                layer: Some(1),
+                part: Some(2),
+                offset: 13,
+                kind: ParseErrorKind::InvalidShape("X".to_string()),
+            }
+        );
+        assert_eq!(
+            Shape::try_from_string("CuCuCuCu:CuCu").unwrap_err(),
+            ShapeParseError {
+                layer: Some(1),
+                part: None,
+                offset: 9,
+                kind: ParseErrorKind::WrongLength {
+                    expected: 8,
+                    found: 4,
+                },
+            }
+        );
+        let err =
+            Shape::try_from_string("Cu------:Cu------:Cu------:Cu------:Cu------").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::TooManyLayers { max: 4, found: 5 });
+        assert_eq!(err.offset, 36);
+        assert_eq!(
+            err.to_string(),
+            "expected at most 4 layers, found 5 in layer 4 (offset 36)"
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("Cr".parse::<SingleItem>().unwrap().color, EColor::Red);
+        assert_eq!("c".parse::<EShape>(), Ok(EShape::Crystal));
+        let shape: Shape = "CrRg----:P-P-P-P-".parse().unwrap();
+        assert_eq!(shape.to_minify_string(), "CrRg----:P-P-P-P-");
+        assert!("Ré------".parse::<SingleLayer>().is_err());
     }
 }
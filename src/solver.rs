@@ -0,0 +1,1989 @@
+use crate::{
+    analyze::{analyze, Impossibility},
+    cost::{CostModel, KindSet, Objective},
+    cutting::Cuttable,
+    packed::{LayerId, ShapeId},
+    pin::PinPushable,
+    reachability::SearchCache,
+    reverse::{CuttableReverse, LayerPattern, StackableReverse},
+    rotate::{Rotatable, RotateDirection},
+    shape::{EColor, EShape, Shape, SingleItem, SingleLayer, SHAPEZ2_DEMENTION},
+};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub fn needed_layers(items: Vec<SingleItem>) -> Vec<SingleLayer> {
+    let mut layers = Vec::new();
+    items.iter().for_each(|item| {
+        let layer = SingleLayer::new_with_shape_color(item.shape, item.color);
+        layers.push(layer);
+    });
+    layers
+}
+
+/// Shapes coming out of the extractors, always full and uncolored
+pub const RAW_SHAPES: [EShape; 4] = [
+    EShape::Circle,
+    EShape::Rectangle,
+    EShape::Windmill,
+    EShape::Star,
+];
+
+/// Kinds of buildings the solver may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperationKind {
+    Rotate,
+    Cut,
+    Swap,
+    Paint,
+    PinPush,
+    Stack,
+}
+
+impl OperationKind {
+    pub const ALL: [OperationKind; 6] = [
+        OperationKind::Rotate,
+        OperationKind::Cut,
+        OperationKind::Swap,
+        OperationKind::Paint,
+        OperationKind::PinPush,
+        OperationKind::Stack,
+    ];
+
+    /// Position of the kind in `ALL`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// One operation of the layer search, with the layers going in and out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    RotateClockwise {
+        input: SingleLayer,
+        output: SingleLayer,
+    },
+    RotateCounterClockwise {
+        input: SingleLayer,
+        output: SingleLayer,
+    },
+    Rotate180 {
+        input: SingleLayer,
+        output: SingleLayer,
+    },
+    /// cut `input` and keep its west half
+    CutWest {
+        input: SingleLayer,
+        output: SingleLayer,
+    },
+    /// cut `input` and keep its east half
+    CutEast {
+        input: SingleLayer,
+        output: SingleLayer,
+    },
+    /// swap the west half of `west` into `east`
+    Swap {
+        east: SingleLayer,
+        west: SingleLayer,
+        output: SingleLayer,
+    },
+}
+
+impl Operation {
+    pub fn kind(&self) -> OperationKind {
+        match self {
+            Operation::RotateClockwise { .. }
+            | Operation::RotateCounterClockwise { .. }
+            | Operation::Rotate180 { .. } => OperationKind::Rotate,
+            Operation::CutWest { .. } | Operation::CutEast { .. } => OperationKind::Cut,
+            Operation::Swap { .. } => OperationKind::Swap,
+        }
+    }
+
+    pub fn inputs(&self) -> Vec<SingleLayer> {
+        match self {
+            Operation::RotateClockwise { input, .. }
+            | Operation::RotateCounterClockwise { input, .. }
+            | Operation::Rotate180 { input, .. }
+            | Operation::CutWest { input, .. }
+            | Operation::CutEast { input, .. } => vec![*input],
+            Operation::Swap { east, west, .. } => vec![*east, *west],
+        }
+    }
+
+    pub fn output(&self) -> SingleLayer {
+        match self {
+            Operation::RotateClockwise { output, .. }
+            | Operation::RotateCounterClockwise { output, .. }
+            | Operation::Rotate180 { output, .. }
+            | Operation::CutWest { output, .. }
+            | Operation::CutEast { output, .. }
+            | Operation::Swap { output, .. } => *output,
+        }
+    }
+
+    /// Every operation the layer search can apply to `layer`, swapping only
+    /// with the layers in `swap_inputs`
+    pub fn successors(layer: &SingleLayer, swap_inputs: &[SingleLayer]) -> Vec<Operation> {
+        let input = *layer;
+        let [west, east] = layer.cutting();
+        let mut operations = vec![
+            Operation::RotateClockwise {
+                input,
+                output: layer.rotate_once(),
+            },
+            Operation::RotateCounterClockwise {
+                input,
+                output: layer.rotate_once_reverse(),
+            },
+            Operation::Rotate180 {
+                input,
+                output: layer.rotate_180(),
+            },
+            Operation::CutWest {
+                input,
+                output: west,
+            },
+            Operation::CutEast {
+                input,
+                output: east,
+            },
+        ];
+        for other in swap_inputs {
+            let [first, second] = layer.swap_with(other);
+            operations.push(Operation::Swap {
+                east: input,
+                west: *other,
+                output: first,
+            });
+            operations.push(Operation::Swap {
+                east: *other,
+                west: input,
+                output: second,
+            });
+        }
+        operations
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::RotateClockwise { input, output } => {
+                write!(f, "rotate {} clockwise -> {}", input, output)
+            }
+            Operation::RotateCounterClockwise { input, output } => {
+                write!(f, "rotate {} counter-clockwise -> {}", input, output)
+            }
+            Operation::Rotate180 { input, output } => {
+                write!(f, "rotate {} by 180 degrees -> {}", input, output)
+            }
+            Operation::CutWest { input, output } => {
+                write!(f, "cut {} and keep the west half -> {}", input, output)
+            }
+            Operation::CutEast { input, output } => {
+                write!(f, "cut {} and keep the east half -> {}", input, output)
+            }
+            Operation::Swap { east, west, output } => write!(
+                f,
+                "swap the west half of {} into {} -> {}",
+                west, east, output
+            ),
+        }
+    }
+}
+
+/// One step of a production plan, with the shapes going in and out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// take a raw shape from an extractor
+    Extract {
+        output: Shape,
+    },
+    /// take one of the start shapes given to the solver
+    Input {
+        output: Shape,
+    },
+    Paint {
+        input: Shape,
+        color: EColor,
+        output: Shape,
+    },
+    /// build a single layer by rotating, cutting and swapping the full layers
+    /// in `inputs`, starting from `start`
+    Layer {
+        inputs: Vec<SingleLayer>,
+        start: SingleLayer,
+        operations: Vec<Operation>,
+    },
+    PinPush {
+        input: Shape,
+        output: Shape,
+    },
+    Stack {
+        bottom: Shape,
+        top: Shape,
+        output: Shape,
+    },
+    /// swap the west half of `west` into `east`
+    Swap {
+        east: Shape,
+        west: Shape,
+        output: Shape,
+    },
+}
+
+impl Step {
+    pub fn output(&self) -> Shape {
+        match self {
+            Step::Extract { output }
+            | Step::Input { output }
+            | Step::Paint { output, .. }
+            | Step::PinPush { output, .. }
+            | Step::Stack { output, .. }
+            | Step::Swap { output, .. } => *output,
+            Step::Layer {
+                start, operations, ..
+            } => layer_shape(operations.last().map_or(*start, Operation::output)),
+        }
+    }
+}
+
+impl Step {
+    /// Buildings used by the step, one entry per building
+    pub fn kinds(&self) -> Vec<OperationKind> {
+        match self {
+            Step::Extract { .. } | Step::Input { .. } => Vec::new(),
+            Step::Paint { .. } => vec![OperationKind::Paint],
+            Step::Layer { operations, .. } => operations.iter().map(Operation::kind).collect(),
+            Step::PinPush { .. } => vec![OperationKind::PinPush],
+            Step::Stack { .. } => vec![OperationKind::Stack],
+            Step::Swap { .. } => vec![OperationKind::Swap],
+        }
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::Extract { output } => write!(f, "extract {}", output),
+            Step::Input { output } => write!(f, "take {}", output),
+            Step::Paint {
+                input,
+                color,
+                output,
+            } => write!(f, "paint {} {} -> {}", input, color, output),
+            Step::Layer {
+                start, operations, ..
+            } => {
+                write!(f, "build a layer from {}", start)?;
+                for operation in operations.iter() {
+                    write!(f, "\n  {}", operation)?;
+                }
+                Ok(())
+            }
+            Step::PinPush { input, output } => write!(f, "push pins under {} -> {}", input, output),
+            Step::Stack {
+                bottom,
+                top,
+                output,
+            } => write!(f, "stack {} on {} -> {}", top, bottom, output),
+            Step::Swap { east, west, output } => write!(
+                f,
+                "swap the west half of {} into {} -> {}",
+                west, east, output
+            ),
+        }
+    }
+}
+
+/// How much searching went into a result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchStats {
+    /// number of states taken out of the open set
+    pub expanded: usize,
+    pub elapsed: Duration,
+}
+
+/// Every step needed to build `goal`, in build order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub goal: Shape,
+    pub steps: Vec<Step>,
+    /// total cost of the buildings under the solver's cost model
+    pub cost: usize,
+    pub stats: SearchStats,
+}
+
+/// Numbered recipe, one step per line
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "recipe for {}", self.goal)?;
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(f, "\n{}. {}", i + 1, step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Operations leading from one of the start shapes to the goal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerSolution {
+    pub start: SingleLayer,
+    pub operations: Vec<Operation>,
+    /// total cost of the operations under the solver's cost model
+    pub cost: usize,
+    pub stats: SearchStats,
+}
+
+impl LayerSolution {
+    /// Every layer on the way, starting with `start`
+    pub fn path(&self) -> Vec<SingleLayer> {
+        let mut path = vec![self.start];
+        path.extend(self.operations.iter().map(Operation::output));
+        path
+    }
+}
+
+impl Display for LayerSolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "start from {}", self.start)?;
+        for (i, operation) in self.operations.iter().enumerate() {
+            write!(f, "\n{}. {}", i + 1, operation)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    /// the goal can't be reached with the allowed operations
+    NotFound(SearchStats),
+    /// gave up after expanding the maximum number of states
+    NodeLimit(SearchStats),
+    /// gave up after running out of time
+    TimeLimit(SearchStats),
+    /// the goal was ruled out before searching, for these reasons
+    Impossible(Vec<Impossibility>),
+}
+
+impl SolveError {
+    pub fn stats(&self) -> SearchStats {
+        match self {
+            SolveError::NotFound(stats)
+            | SolveError::NodeLimit(stats)
+            | SolveError::TimeLimit(stats) => *stats,
+            SolveError::Impossible(_) => SearchStats::default(),
+        }
+    }
+}
+
+impl Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stats = self.stats();
+        match self {
+            SolveError::Impossible(reasons) => {
+                write!(f, "impossible to build")?;
+                for reason in reasons {
+                    write!(f, "\n- {}", reason)?;
+                }
+                return Ok(());
+            }
+            SolveError::NotFound(_) => write!(f, "no solution found")?,
+            SolveError::NodeLimit(_) => write!(f, "node limit reached")?,
+            SolveError::TimeLimit(_) => write!(f, "time limit reached")?,
+        }
+        write!(
+            f,
+            " after expanding {} states in {:?}",
+            stats.expanded, stats.elapsed
+        )
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// Node of the search tree. Paths are kept as parent links into the node
+/// arena instead of being copied into every open state.
+struct Node {
+    layer: LayerId,
+    /// index of the parent node, `NO_PARENT` for start shapes
+    parent: u32,
+    /// index of the operation in `Operation::successors` of the parent
+    successor: u16,
+}
+
+const NO_PARENT: u32 = u32::MAX;
+
+/// Operation taking a layer one step closer to the goal in the backward
+/// search, swaps name the start shape they swap with
+#[derive(Debug, Clone, Copy)]
+enum Move {
+    RotateClockwise,
+    RotateCounterClockwise,
+    Rotate180,
+    CutWest,
+    CutEast,
+    /// the layer keeps its east half and takes the west half of the input
+    SwapAsEast(usize),
+    /// the layer gives its west half to the east half of the input
+    SwapAsWest(usize),
+}
+
+impl Move {
+    fn apply(self, layer: &SingleLayer, swap_inputs: &[SingleLayer]) -> Operation {
+        let input = *layer;
+        match self {
+            Move::RotateClockwise => Operation::RotateClockwise {
+                input,
+                output: layer.rotate_once(),
+            },
+            Move::RotateCounterClockwise => Operation::RotateCounterClockwise {
+                input,
+                output: layer.rotate_once_reverse(),
+            },
+            Move::Rotate180 => Operation::Rotate180 {
+                input,
+                output: layer.rotate_180(),
+            },
+            Move::CutWest => Operation::CutWest {
+                input,
+                output: layer.cutting()[0],
+            },
+            Move::CutEast => Operation::CutEast {
+                input,
+                output: layer.cutting()[1],
+            },
+            Move::SwapAsEast(i) => Operation::Swap {
+                east: input,
+                west: swap_inputs[i],
+                output: SingleLayer::swap(layer, &swap_inputs[i])[0],
+            },
+            Move::SwapAsWest(i) => Operation::Swap {
+                east: swap_inputs[i],
+                west: input,
+                output: SingleLayer::swap(&swap_inputs[i], layer)[0],
+            },
+        }
+    }
+}
+
+/// Add a layer found by the forward search to the arena unless it was seen
+/// before, noting every backward pattern it matches
+fn visit_forward(
+    nodes: &mut Vec<Node>,
+    forward: &mut HashMap<LayerPattern, u32>,
+    backward_index: &HashMap<LayerPattern, u32>,
+    meetings: &mut Vec<(u32, u32)>,
+    node: Node,
+    budget: &Budget,
+) -> Result<Option<u32>, SolveError> {
+    let layer = SingleLayer::from(node.layer);
+    if forward.contains_key(&LayerPattern::exact(&layer)) {
+        return Ok(None);
+    }
+    let index = budget.node_index(nodes.len())?;
+    nodes.push(node);
+    for mask in 0..1 << SHAPEZ2_DEMENTION {
+        let pattern = LayerPattern::masked(&layer, mask);
+        forward.entry(pattern).or_insert(index);
+        if let Some(&back) = backward_index.get(&pattern) {
+            meetings.push((index, back));
+        }
+    }
+    Ok(Some(index))
+}
+
+/// Node of the backward search: layers matching `pattern` reach the
+/// pattern of `child` with `step`
+struct PatternNode {
+    pattern: LayerPattern,
+    /// `NO_PARENT` for the goal itself
+    child: u32,
+    step: Move,
+}
+
+/// Entry of the open set, pointing into the node arena
+#[derive(Clone, Copy, Eq, PartialEq)]
+struct State {
+    node: u32,
+    /// building kinds on the path, only tracked when they affect the cost
+    used: KindSet,
+    cost: usize,
+    heuristic: usize,
+}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.cost + other.heuristic).cmp(&(self.cost + self.heuristic))
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Lower bound on the operations still needed to turn `layer` into `goal`.
+///
+/// Rotating never changes which parts a layer has relative to each other,
+/// and cutting or swapping replaces at most one half, so every operation
+/// removes at most half a layer of mismatches, counted under the best
+/// rotation. A layer that matches only after rotating needs one more step.
+/// Crystals can shatter across both halves, so layers holding them only
+/// count as one step away. The bound is consistent, so the first path found
+/// to the goal is a shortest one.
+fn heuristic(layer: &SingleLayer, goal: &SingleLayer) -> usize {
+    if layer.items.iter().any(|item| item.shape == EShape::Crystal) {
+        return usize::from(layer != goal);
+    }
+    let half = SHAPEZ2_DEMENTION / 2;
+    let mismatches = (0..SHAPEZ2_DEMENTION)
+        .map(|times| {
+            let rotated = layer.rotate(RotateDirection::Clockwise, times);
+            (0..SHAPEZ2_DEMENTION)
+                .filter(|&j| rotated.items[j] != goal.items[j])
+                .count()
+        })
+        .min()
+        .unwrap_or_default();
+    let changes = mismatches.div_ceil(half);
+    if changes == 0 && layer != goal {
+        1
+    } else {
+        changes
+    }
+}
+
+/// Node and time limits shared by every search of one solve
+struct Budget {
+    expanded: usize,
+    started: Instant,
+    max_nodes: Option<usize>,
+    time_limit: Option<Duration>,
+    /// states expanded by all the threads searching ahead of the planner
+    #[cfg(feature = "parallel")]
+    shared: Option<Arc<std::sync::atomic::AtomicUsize>>,
+}
+
+impl Budget {
+    fn stats(&self) -> SearchStats {
+        SearchStats {
+            expanded: self.expanded,
+            elapsed: self.started.elapsed(),
+        }
+    }
+
+    fn expand(&mut self) -> Result<(), SolveError> {
+        if self.max_nodes.is_some_and(|max| self.expanded >= max) {
+            return Err(SolveError::NodeLimit(self.stats()));
+        }
+        #[cfg(feature = "parallel")]
+        if let Some(shared) = &self.shared {
+            let all = shared.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            if self.max_nodes.is_some_and(|max| all >= max) {
+                return Err(SolveError::NodeLimit(self.stats()));
+            }
+        }
+        if self
+            .time_limit
+            .is_some_and(|limit| self.started.elapsed() >= limit)
+        {
+            return Err(SolveError::TimeLimit(self.stats()));
+        }
+        self.expanded += 1;
+        Ok(())
+    }
+
+    /// Budget for a search on another thread, started with this one and
+    /// counting its states in `shared` as well, so the threads together stop
+    /// at the node limit
+    #[cfg(feature = "parallel")]
+    fn for_thread(&self, shared: &Arc<std::sync::atomic::AtomicUsize>) -> Budget {
+        Budget {
+            expanded: 0,
+            started: self.started,
+            max_nodes: self.max_nodes,
+            time_limit: self.time_limit,
+            shared: Some(shared.clone()),
+        }
+    }
+
+    /// Count the states of a search done elsewhere, failing the same way the
+    /// search would have failed when run under this budget
+    fn charge(&mut self, expanded: usize) -> Result<(), SolveError> {
+        if let Some(max) = self.max_nodes.filter(|&max| self.expanded + expanded > max) {
+            self.expanded = max;
+            return Err(SolveError::NodeLimit(self.stats()));
+        }
+        if expanded > 0
+            && self
+                .time_limit
+                .is_some_and(|limit| self.started.elapsed() >= limit)
+        {
+            return Err(SolveError::TimeLimit(self.stats()));
+        }
+        self.expanded += expanded;
+        Ok(())
+    }
+
+    /// Index of the next node in an arena of `len` nodes, running out of
+    /// indices counts as reaching the node limit
+    fn node_index(&self, len: usize) -> Result<u32, SolveError> {
+        u32::try_from(len)
+            .ok()
+            .filter(|&index| index != NO_PARENT)
+            .ok_or_else(|| SolveError::NodeLimit(self.stats()))
+    }
+
+    /// Position `i` in the successors of a layer as stored in a node
+    fn successor_index(&self, i: usize) -> Result<u16, SolveError> {
+        u16::try_from(i).map_err(|_| SolveError::NodeLimit(self.stats()))
+    }
+}
+
+/// Outcome of a layer search together with the number of states it expanded
+type LayerSearch = (Option<(SingleLayer, Vec<Operation>)>, usize);
+
+/// One way of building a goal, see `Solver::split`
+type Split = fn(&Solver, &Shape, &mut Known, &mut Budget) -> Result<Option<Vec<Step>>, SolveError>;
+
+/// Everything found so far while solving one goal
+#[derive(Default)]
+struct Known {
+    plans: HashMap<ShapeId, Option<Vec<Step>>>,
+    /// goals being split right now, met again they can't be built that way
+    building: HashSet<ShapeId>,
+    /// goals from `building` the current split ran into
+    cycles: HashSet<ShapeId>,
+    /// layer searches done ahead of time by the parallel solver
+    layers: HashMap<LayerId, LayerSearch>,
+}
+
+/// Searches for ways to build shapes.
+///
+/// ```
+/// use shapez2_calc::{shape::Shape, solver::Solver};
+///
+/// let goal = Shape::try_from_string("CuCuCuCu:RrRr----").unwrap();
+/// let plan = Solver::new().max_nodes(100_000).solve(&goal).unwrap();
+/// assert_eq!(plan.steps.last().unwrap().output(), goal);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solver {
+    start_shapes: Option<Vec<SingleLayer>>,
+    operations: HashSet<OperationKind>,
+    cost_model: CostModel,
+    max_nodes: Option<usize>,
+    time_limit: Option<Duration>,
+    bidirectional: bool,
+    cache: Option<Arc<SearchCache>>,
+    #[cfg(feature = "parallel")]
+    threads: usize,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Solver {
+            start_shapes: None,
+            operations: OperationKind::ALL.into_iter().collect(),
+            cost_model: CostModel::default(),
+            max_nodes: None,
+            time_limit: None,
+            bidirectional: false,
+            cache: None,
+            #[cfg(feature = "parallel")]
+            threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Self::default()
+    }
+
+    /// Full layers the layer search starts from and swaps with. By default
+    /// they are the raw shapes painted in the colors of the goal.
+    pub fn start_shapes(mut self, start_shapes: Vec<SingleLayer>) -> Solver {
+        self.start_shapes = Some(start_shapes);
+        self
+    }
+
+    pub fn operations(mut self, operations: impl IntoIterator<Item = OperationKind>) -> Solver {
+        self.operations = operations.into_iter().collect();
+        self
+    }
+
+    /// Prices of the buildings. The layer search looks for the cheapest path
+    /// and the planner keeps the cheapest way of splitting each goal.
+    pub fn cost_model(mut self, cost_model: CostModel) -> Solver {
+        self.cost_model = cost_model;
+        self
+    }
+
+    pub fn max_nodes(mut self, max_nodes: usize) -> Solver {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> Solver {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    /// Search layers from both ends at once, forward from the start shapes
+    /// and backward from the goal, until the two searches meet. Paths have
+    /// the fewest operations, the cost model only prices them.
+    pub fn bidirectional(mut self, bidirectional: bool) -> Solver {
+        self.bidirectional = bidirectional;
+        self
+    }
+
+    /// Look layers up in a cache of layer search results before searching.
+    /// A recipe from the cache is used when it only needs the start shapes
+    /// of the search, and a layer missing from a cache built from more start
+    /// shapes is known to be out of reach of the search. The cache is skipped
+    /// under any objective but `Objective::FewestMachines`.
+    pub fn search_cache(mut self, cache: Arc<SearchCache>) -> Solver {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Number of threads running layer searches ahead of the planner, 1 to
+    /// solve sequentially. The threads search the goal's layers and their
+    /// halves, each search on one thread; everything else the planner does,
+    /// including the searches it runs itself, stays on the calling thread.
+    /// All of them share the node and time limits with the planner, and
+    /// plans are the same for any number of threads.
+    #[cfg(feature = "parallel")]
+    pub fn threads(mut self, threads: usize) -> Solver {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Crystal parts of `goal` that no start shape can supply. Cutting,
+    /// swapping and stacking only move crystals around, and the raw shapes
+    /// the planner starts from by default have none.
+    fn missing_crystals(&self, goal: &Shape) -> Vec<Impossibility> {
+        let is_crystal = |item: &SingleItem| item.shape == EShape::Crystal;
+        if self
+            .start_shapes
+            .iter()
+            .flatten()
+            .any(|layer| layer.items.iter().any(is_crystal))
+        {
+            return Vec::new();
+        }
+        let mut reasons = Vec::new();
+        for i in 0..goal.layer_height() {
+            for j in 0..SHAPEZ2_DEMENTION {
+                if is_crystal(&goal[i][j]) {
+                    reasons.push(Impossibility::NoCrystalSource((i, j)));
+                }
+            }
+        }
+        reasons
+    }
+
+    fn allows(&self, operation: OperationKind) -> bool {
+        self.operations.contains(&operation)
+    }
+
+    fn budget(&self) -> Budget {
+        Budget {
+            expanded: 0,
+            started: Instant::now(),
+            max_nodes: self.max_nodes,
+            time_limit: self.time_limit,
+            #[cfg(feature = "parallel")]
+            shared: None,
+        }
+    }
+
+    /// Find the layers leading from the start shapes to a single layer goal
+    pub fn solve_layer(&self, goal: &SingleLayer) -> Result<LayerSolution, SolveError> {
+        let mut budget = self.budget();
+        let start_shapes = match &self.start_shapes {
+            Some(start_shapes) => start_shapes.clone(),
+            None => needed_layers(layer_items(goal)),
+        };
+        match self.search(&start_shapes, goal, &mut budget)? {
+            Some((start, operations)) => Ok(LayerSolution {
+                start,
+                cost: self
+                    .cost_model
+                    .total(operations.iter().map(Operation::kind)),
+                operations,
+                stats: budget.stats(),
+            }),
+            None => Err(SolveError::NotFound(budget.stats())),
+        }
+    }
+
+    /// Find a production plan for a whole shape.
+    ///
+    /// The goal is split recursively: a bottom layer of pins comes from the
+    /// pin pusher, a taller shape is stacked from a bottom and a top part,
+    /// and a shape can be put together from its east and west halves with
+    /// the swapper. Single layers without pins are built by the layer search
+    /// from the start shapes. Every split is checked by running the
+    /// operation, so the plan reproduces the goal exactly. Of the splits that
+    /// work, the one whose plan is cheapest under the cost model is kept, the
+    /// parts being planned on their own.
+    ///
+    /// Goals that `analyze` proves impossible fail right away, and so do
+    /// goals with crystals when no start shape has any.
+    pub fn solve(&self, goal: &Shape) -> Result<Plan, SolveError> {
+        let mut reasons = analyze(goal);
+        reasons.extend(self.missing_crystals(goal));
+        if !reasons.is_empty() {
+            return Err(SolveError::Impossible(reasons));
+        }
+        let mut budget = self.budget();
+        let mut known = Known::default();
+        #[cfg(feature = "parallel")]
+        if self.threads > 1 {
+            self.prefetch_layers(goal, &mut known, &budget);
+        }
+        match self.build(goal, &mut known, &mut budget)? {
+            Some(steps) => Ok(Plan {
+                goal: *goal,
+                cost: self.steps_cost(&steps),
+                steps,
+                stats: budget.stats(),
+            }),
+            None => Err(SolveError::NotFound(budget.stats())),
+        }
+    }
+
+    fn search(
+        &self,
+        start_shapes: &[SingleLayer],
+        goal: &SingleLayer,
+        budget: &mut Budget,
+    ) -> Result<Option<(SingleLayer, Vec<Operation>)>, SolveError> {
+        if let Some(found) = self.look_up(start_shapes, goal) {
+            return Ok(found);
+        }
+        if self.bidirectional {
+            self.meet_in_middle(start_shapes, goal, budget)
+        } else {
+            self.a_star(start_shapes, goal, budget)
+        }
+    }
+
+    /// Answer from the search cache, `None` if it can't tell. The cache's
+    /// recipes have the fewest operations, so it is only used when that is
+    /// what the cost model asks for.
+    fn look_up(
+        &self,
+        start_shapes: &[SingleLayer],
+        goal: &SingleLayer,
+    ) -> Option<Option<(SingleLayer, Vec<Operation>)>> {
+        if self.cost_model.objective != Objective::default() {
+            return None;
+        }
+        let cache = self.cache.as_ref()?;
+        let swap_inputs = if self.allows(OperationKind::Swap) {
+            start_shapes
+        } else {
+            &[]
+        };
+        match cache.recipe(goal) {
+            Ok(Some((start, operations))) => {
+                let mut current = start;
+                for operation in operations.iter() {
+                    if !self.allows(operation.kind())
+                        || !Operation::successors(&current, swap_inputs).contains(operation)
+                    {
+                        return None;
+                    }
+                    current = operation.output();
+                }
+                start_shapes
+                    .contains(&start)
+                    .then_some(Some((start, operations)))
+            }
+            Ok(None) => start_shapes
+                .iter()
+                .all(|layer| cache.start_shapes().contains(layer))
+                .then_some(None),
+            // a damaged cache can't tell, the layer is searched
+            Err(_) => None,
+        }
+    }
+
+    fn a_star(
+        &self,
+        start_shapes: &[SingleLayer],
+        goal: &SingleLayer,
+        budget: &mut Budget,
+    ) -> Result<Option<(SingleLayer, Vec<Operation>)>, SolveError> {
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut open_set = BinaryHeap::new();
+        let mut closed_set = HashSet::new();
+        let track_kinds = self.cost_model.objective == Objective::FewestBuildingTypes;
+        // every operation left costs at least this much
+        let min_cost = self.cost_model.min_cost(
+            [
+                OperationKind::Rotate,
+                OperationKind::Cut,
+                OperationKind::Swap,
+            ]
+            .into_iter()
+            .filter(|&kind| self.allows(kind)),
+        );
+        let swap_inputs = if self.allows(OperationKind::Swap) {
+            start_shapes
+        } else {
+            &[]
+        };
+
+        for layer in start_shapes {
+            open_set.push(State {
+                node: budget.node_index(nodes.len())?,
+                used: KindSet::default(),
+                cost: 0,
+                heuristic: heuristic(layer, goal) * min_cost,
+            });
+            nodes.push(Node {
+                layer: LayerId::from(*layer),
+                parent: NO_PARENT,
+                successor: 0,
+            });
+        }
+
+        let goal_packed = LayerId::from(*goal);
+        while let Some(current) = open_set.pop() {
+            let packed = nodes[current.node as usize].layer;
+            if packed == goal_packed {
+                return Ok(Some(Self::trace(&nodes, current.node, swap_inputs)));
+            }
+
+            if !closed_set.insert((packed, current.used)) {
+                continue;
+            }
+            budget.expand()?;
+
+            let layer = SingleLayer::from(packed);
+            for (i, operation) in Operation::successors(&layer, swap_inputs)
+                .into_iter()
+                .enumerate()
+            {
+                let kind = operation.kind();
+                let new_state = LayerId::from(operation.output());
+                let mut new_used = current.used;
+                if track_kinds {
+                    new_used.insert(kind);
+                }
+                if self.allows(kind) && !closed_set.contains(&(new_state, new_used)) {
+                    open_set.push(State {
+                        node: budget.node_index(nodes.len())?,
+                        used: new_used,
+                        cost: current.cost + self.cost_model.step_cost(kind, current.used),
+                        heuristic: heuristic(&operation.output(), goal) * min_cost,
+                    });
+                    nodes.push(Node {
+                        layer: new_state,
+                        parent: current.node,
+                        successor: budget.successor_index(i)?,
+                    });
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Breadth-first search from both ends. The backward search runs over
+    /// patterns, since a cut or a swap can come from any layer that agrees
+    /// on the half it keeps. A forward layer matching a backward pattern is
+    /// replayed along the backward chain, which also rules out meetings
+    /// spoiled by crystals shattering.
+    fn meet_in_middle(
+        &self,
+        start_shapes: &[SingleLayer],
+        goal: &SingleLayer,
+        budget: &mut Budget,
+    ) -> Result<Option<(SingleLayer, Vec<Operation>)>, SolveError> {
+        let swap_inputs = if self.allows(OperationKind::Swap) {
+            start_shapes
+        } else {
+            &[]
+        };
+
+        let mut nodes: Vec<Node> = Vec::new();
+        // every forward layer under each way of opening its parts
+        let mut forward: HashMap<LayerPattern, u32> = HashMap::new();
+        let mut forward_frontier = Vec::new();
+        let mut backward: Vec<PatternNode> = vec![PatternNode {
+            pattern: LayerPattern::exact(goal),
+            child: NO_PARENT,
+            step: Move::Rotate180,
+        }];
+        let mut backward_index: HashMap<LayerPattern, u32> =
+            HashMap::from([(LayerPattern::exact(goal), 0)]);
+        let mut backward_frontier = vec![0];
+        let mut meetings = Vec::new();
+
+        for layer in start_shapes {
+            let node = Node {
+                layer: LayerId::from(*layer),
+                parent: NO_PARENT,
+                successor: 0,
+            };
+            if let Some(index) = visit_forward(
+                &mut nodes,
+                &mut forward,
+                &backward_index,
+                &mut meetings,
+                node,
+                budget,
+            )? {
+                forward_frontier.push(index);
+            }
+        }
+
+        loop {
+            // keep the shortest meeting that survives replaying it
+            let mut best: Option<(SingleLayer, Vec<Operation>)> = None;
+            for &(front, back) in meetings.iter() {
+                if let Some(path) = self.join(&nodes, front, &backward, back, swap_inputs, goal) {
+                    if best.as_ref().is_none_or(|best| path.1.len() < best.1.len()) {
+                        best = Some(path);
+                    }
+                }
+            }
+            if best.is_some() {
+                return Ok(best);
+            }
+            meetings.clear();
+            if forward_frontier.is_empty() || backward_frontier.is_empty() {
+                return Ok(None);
+            }
+
+            if forward_frontier.len() <= backward_frontier.len() {
+                let mut next = Vec::new();
+                for &current in forward_frontier.iter() {
+                    budget.expand()?;
+                    let layer = SingleLayer::from(nodes[current as usize].layer);
+                    for (i, operation) in Operation::successors(&layer, swap_inputs)
+                        .into_iter()
+                        .enumerate()
+                    {
+                        if !self.allows(operation.kind()) {
+                            continue;
+                        }
+                        let node = Node {
+                            layer: LayerId::from(operation.output()),
+                            parent: current,
+                            successor: budget.successor_index(i)?,
+                        };
+                        if let Some(index) = visit_forward(
+                            &mut nodes,
+                            &mut forward,
+                            &backward_index,
+                            &mut meetings,
+                            node,
+                            budget,
+                        )? {
+                            next.push(index);
+                        }
+                    }
+                }
+                forward_frontier = next;
+            } else {
+                let mut next = Vec::new();
+                for &current in backward_frontier.iter() {
+                    budget.expand()?;
+                    let pattern = backward[current as usize].pattern;
+                    for (preimage, step) in self.preimages(&pattern, swap_inputs) {
+                        if backward_index.contains_key(&preimage) {
+                            continue;
+                        }
+                        let index = budget.node_index(backward.len())?;
+                        backward.push(PatternNode {
+                            pattern: preimage,
+                            child: current,
+                            step,
+                        });
+                        backward_index.insert(preimage, index);
+                        if let Some(&front) = forward.get(&preimage) {
+                            meetings.push((front, index));
+                        }
+                        next.push(index);
+                    }
+                }
+                backward_frontier = next;
+            }
+        }
+    }
+
+    /// Patterns of the layers that one allowed move turns into `pattern`
+    fn preimages(
+        &self,
+        pattern: &LayerPattern,
+        swap_inputs: &[SingleLayer],
+    ) -> Vec<(LayerPattern, Move)> {
+        let mut preimages = Vec::new();
+        if self.allows(OperationKind::Rotate) {
+            // rotations are undone by turning the other way
+            preimages.push((pattern.rotate_once_reverse(), Move::RotateClockwise));
+            preimages.push((pattern.rotate_once(), Move::RotateCounterClockwise));
+            preimages.push((pattern.rotate_180(), Move::Rotate180));
+        }
+        if self.allows(OperationKind::Cut) {
+            let [west, east] = pattern.cutting_preimages();
+            preimages.extend(west.map(|west| (west, Move::CutWest)));
+            preimages.extend(east.map(|east| (east, Move::CutEast)));
+        }
+        for (i, other) in swap_inputs.iter().enumerate() {
+            let [as_east, as_west] = pattern.swap_preimages(other);
+            preimages.extend(as_east.map(|pattern| (pattern, Move::SwapAsEast(i))));
+            preimages.extend(as_west.map(|pattern| (pattern, Move::SwapAsWest(i))));
+        }
+        preimages
+    }
+
+    /// Replay the backward chain from `back` on the forward layer `front`,
+    /// `None` if the real operations leave the chain
+    fn join(
+        &self,
+        nodes: &[Node],
+        front: u32,
+        backward: &[PatternNode],
+        mut back: u32,
+        swap_inputs: &[SingleLayer],
+        goal: &SingleLayer,
+    ) -> Option<(SingleLayer, Vec<Operation>)> {
+        let (start, mut operations) = Self::trace(nodes, front, swap_inputs);
+        let mut layer = SingleLayer::from(nodes[front as usize].layer);
+        while backward[back as usize].child != NO_PARENT {
+            let PatternNode { step, child, .. } = backward[back as usize];
+            let operation = step.apply(&layer, swap_inputs);
+            layer = operation.output();
+            if !backward[child as usize].pattern.matches(&layer) {
+                return None;
+            }
+            operations.push(operation);
+            back = child;
+        }
+        (layer == *goal).then_some((start, operations))
+    }
+
+    /// Follow the parent links from `node` back to its start shape
+    fn trace(
+        nodes: &[Node],
+        mut node: u32,
+        swap_inputs: &[SingleLayer],
+    ) -> (SingleLayer, Vec<Operation>) {
+        let mut operations = Vec::new();
+        while nodes[node as usize].parent != NO_PARENT {
+            let Node {
+                parent, successor, ..
+            } = nodes[node as usize];
+            let from = SingleLayer::from(nodes[parent as usize].layer);
+            operations.push(Operation::successors(&from, swap_inputs)[successor as usize]);
+            node = parent;
+        }
+        operations.reverse();
+        (nodes[node as usize].layer.into(), operations)
+    }
+
+    fn build(
+        &self,
+        goal: &Shape,
+        known: &mut Known,
+        budget: &mut Budget,
+    ) -> Result<Option<Vec<Step>>, SolveError> {
+        let id = ShapeId::from(*goal);
+        if let Some(steps) = known.plans.get(&id) {
+            return Ok(steps.clone());
+        }
+        if known.building.contains(&id) {
+            known.cycles.insert(id);
+            return Ok(None);
+        }
+
+        known.building.insert(id);
+        let outer = std::mem::take(&mut known.cycles);
+        let steps = self.split(goal, known, budget);
+        known.building.remove(&id);
+        known.cycles.remove(&id);
+        let steps = steps?;
+        // a failure that ran into a goal still being built may succeed once
+        // that goal is done, so only plans and final failures are kept
+        if steps.is_some() || known.cycles.is_empty() {
+            known.plans.insert(id, steps.clone());
+        }
+        known.cycles.extend(outer);
+        Ok(steps)
+    }
+
+    /// Try every way of building `goal` and keep the cheapest plan under the
+    /// cost model. Once a plan is found, running out of budget only ends the
+    /// search for a cheaper one.
+    fn split(
+        &self,
+        goal: &Shape,
+        known: &mut Known,
+        budget: &mut Budget,
+    ) -> Result<Option<Vec<Step>>, SolveError> {
+        let ways: [(Option<OperationKind>, Split); 4] = [
+            (None, Self::build_layer),
+            (Some(OperationKind::PinPush), Self::build_pin_push),
+            (Some(OperationKind::Stack), Self::build_stack),
+            (Some(OperationKind::Swap), Self::build_swap),
+        ];
+        let mut best = None;
+        for (kind, way) in ways {
+            if kind.is_some_and(|kind| !self.allows(kind)) {
+                continue;
+            }
+            match way(self, goal, known, budget) {
+                Ok(steps) => best = self.cheaper(best, steps),
+                Err(_) if best.is_some() => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(best)
+    }
+
+    fn steps_cost(&self, steps: &[Step]) -> usize {
+        self.cost_model.total(steps.iter().flat_map(Step::kinds))
+    }
+
+    /// The cheaper of two plans, the first one on a tie
+    fn cheaper(&self, best: Option<Vec<Step>>, steps: Option<Vec<Step>>) -> Option<Vec<Step>> {
+        match (best, steps) {
+            (Some(best), Some(steps)) if self.steps_cost(&steps) < self.steps_cost(&best) => {
+                Some(steps)
+            }
+            (best, steps) => best.or(steps),
+        }
+    }
+
+    fn build_layer(
+        &self,
+        goal: &Shape,
+        known: &mut Known,
+        budget: &mut Budget,
+    ) -> Result<Option<Vec<Step>>, SolveError> {
+        if goal.layer_height() != 1 {
+            return Ok(None);
+        }
+        let layer = goal[0];
+        let Some((mut steps, inputs)) = self.layer_inputs(&layer) else {
+            return Ok(None);
+        };
+
+        if !inputs.contains(&layer) {
+            let found = match known.layers.get(&LayerId::from(layer)) {
+                Some((found, expanded)) => {
+                    budget.charge(*expanded)?;
+                    found.clone()
+                }
+                None => self.search(&inputs, &layer, budget)?,
+            };
+            let Some((start, operations)) = found else {
+                return Ok(None);
+            };
+            steps.push(Step::Layer {
+                inputs,
+                start,
+                operations,
+            });
+        }
+        Ok(Some(steps))
+    }
+
+    /// Full layers to build `layer` from, with the steps providing them.
+    /// `None` if the layer can't come from the extractors.
+    fn layer_inputs(&self, layer: &SingleLayer) -> Option<(Vec<Step>, Vec<SingleLayer>)> {
+        let mut steps = Vec::new();
+        let inputs = match &self.start_shapes {
+            Some(start_shapes) => {
+                for input in start_shapes.iter() {
+                    steps.push(Step::Input {
+                        output: layer_shape(*input),
+                    });
+                }
+                start_shapes.clone()
+            }
+            None => {
+                let items = layer_items(layer);
+                // the painter only has the colors mixed from primary paint
+                if items.iter().any(|item| {
+                    !RAW_SHAPES.contains(&item.shape)
+                        || item.color == EColor::Empty
+                        || (item.color != EColor::Uncolored
+                            && (!self.allows(OperationKind::Paint)
+                                || item.color.primary_components().is_empty()))
+                }) {
+                    return None;
+                }
+                let inputs = needed_layers(items);
+                for input in inputs.iter() {
+                    let raw = Shape::new_simple(input[0].shape, EColor::Uncolored);
+                    steps.push(Step::Extract { output: raw });
+                    if input[0].color != EColor::Uncolored {
+                        steps.push(Step::Paint {
+                            input: raw,
+                            color: input[0].color,
+                            output: layer_shape(*input),
+                        });
+                    }
+                }
+                inputs
+            }
+        };
+        Some((steps, inputs))
+    }
+
+    /// Run the layer searches the planner is likely to need on several
+    /// threads: one search for each distinct layer of the goal and for each
+    /// of its halves. The searches are independent of each other, and one
+    /// search never runs on more than one thread: a layer search seldom
+    /// expands more than a few dozen states, so splitting one up would cost
+    /// more than it saves.
+    ///
+    /// The threads share the clock of `budget` and a single count of states,
+    /// and stop once either limit is reached. Each search gives the same
+    /// result wherever it runs, and the planner charges its states to
+    /// `budget` when it uses it, so the plan doesn't depend on the number of
+    /// threads.
+    #[cfg(feature = "parallel")]
+    fn prefetch_layers(&self, goal: &Shape, known: &mut Known, budget: &Budget) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        let mut jobs: Vec<(SingleLayer, Vec<SingleLayer>)> = Vec::new();
+        for i in 0..goal.layer_height() {
+            for layer in [goal[i]].into_iter().chain(goal[i].cutting()) {
+                if !layer.is_some() || jobs.iter().any(|(known, _)| *known == layer) {
+                    continue;
+                }
+                if let Some((_, inputs)) = self.layer_inputs(&layer) {
+                    if !inputs.contains(&layer) {
+                        jobs.push((layer, inputs));
+                    }
+                }
+            }
+        }
+
+        let next = AtomicUsize::new(0);
+        let shared = Arc::new(AtomicUsize::new(0));
+        let found = Mutex::new(Vec::new());
+        std::thread::scope(|scope| {
+            for _ in 0..self.threads.min(jobs.len()) {
+                scope.spawn(|| {
+                    while let Some((layer, inputs)) = jobs.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let mut budget = budget.for_thread(&shared);
+                        // searches cut short by a limit are redone by the planner
+                        let Ok(path) = self.search(inputs, layer, &mut budget) else {
+                            break;
+                        };
+                        let search = (path, budget.expanded);
+                        found.lock().unwrap().push((LayerId::from(*layer), search));
+                    }
+                });
+            }
+        });
+        known.layers.extend(found.into_inner().unwrap());
+    }
+
+    fn build_pin_push(
+        &self,
+        goal: &Shape,
+        known: &mut Known,
+        budget: &mut Budget,
+    ) -> Result<Option<Vec<Step>>, SolveError> {
+        let mut upper = Shape::default();
+        for i in 1..goal.layer_height() {
+            upper[i - 1] = goal[i];
+        }
+        if upper.layer_height() == 0 || upper.pin_push() != *goal {
+            return Ok(None);
+        }
+        let Some(mut steps) = self.build(&upper, known, budget)? else {
+            return Ok(None);
+        };
+        steps.push(Step::PinPush {
+            input: upper,
+            output: *goal,
+        });
+        Ok(Some(steps))
+    }
+
+    fn build_stack(
+        &self,
+        goal: &Shape,
+        known: &mut Known,
+        budget: &mut Budget,
+    ) -> Result<Option<Vec<Step>>, SolveError> {
+        let mut best = None;
+        for (bottom, top) in goal.unstack() {
+            match self.build_stacked(goal, bottom, top, known, budget) {
+                Ok(steps) => best = self.cheaper(best, steps),
+                Err(_) if best.is_some() => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(best)
+    }
+
+    fn build_stacked(
+        &self,
+        goal: &Shape,
+        bottom: Shape,
+        top: Shape,
+        known: &mut Known,
+        budget: &mut Budget,
+    ) -> Result<Option<Vec<Step>>, SolveError> {
+        let Some(mut steps) = self.build(&bottom, known, budget)? else {
+            return Ok(None);
+        };
+        let Some(top_steps) = self.build(&top, known, budget)? else {
+            return Ok(None);
+        };
+        steps.extend(top_steps);
+        steps.push(Step::Stack {
+            bottom,
+            top,
+            output: *goal,
+        });
+        Ok(Some(steps))
+    }
+
+    fn build_swap(
+        &self,
+        goal: &Shape,
+        known: &mut Known,
+        budget: &mut Budget,
+    ) -> Result<Option<Vec<Step>>, SolveError> {
+        let [west, east] = goal.cutting();
+        if east.layer_height() == 0 || west.layer_height() == 0 {
+            return Ok(None);
+        }
+        let output = Shape::swap(&east, &west)[0];
+        if output != *goal {
+            return Ok(None);
+        }
+        let Some(mut steps) = self.build(&east, known, budget)? else {
+            return Ok(None);
+        };
+        let Some(west_steps) = self.build(&west, known, budget)? else {
+            return Ok(None);
+        };
+        steps.extend(west_steps);
+        steps.push(Step::Swap { east, west, output });
+        Ok(Some(steps))
+    }
+}
+
+fn layer_shape(layer: SingleLayer) -> Shape {
+    let mut shape = Shape::default();
+    shape[0] = layer;
+    shape
+}
+
+/// Distinct non-empty items of a layer
+fn layer_items(layer: &SingleLayer) -> Vec<SingleItem> {
+    let mut items: Vec<SingleItem> = Vec::new();
+    for item in layer.items {
+        if item.shape != EShape::Empty && !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        paint::Paintable,
+        shape::{layer, shape},
+        stack::Stackable,
+    };
+    use pretty_assertions::assert_eq;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Run every step and check the plan ends with the goal
+    fn check_plan(plan: &Plan) {
+        for step in plan.steps.iter() {
+            match step {
+                Step::Extract { output } => {
+                    assert!(RAW_SHAPES
+                        .iter()
+                        .any(|&raw| *output == Shape::new_simple(raw, EColor::Uncolored)));
+                }
+                Step::Input { .. } => {}
+                Step::Paint {
+                    input,
+                    color,
+                    output,
+                } => assert_eq!(input.paint_top(*color), *output),
+                Step::Layer {
+                    inputs,
+                    start,
+                    operations,
+                } => {
+                    assert!(inputs.contains(start));
+                    let mut current = *start;
+                    for operation in operations.iter() {
+                        assert!(operation.inputs().contains(&current));
+                        assert!(Operation::successors(&current, inputs).contains(operation));
+                        current = operation.output();
+                    }
+                }
+                Step::PinPush { input, output } => assert_eq!(input.pin_push(), *output),
+                Step::Stack {
+                    bottom,
+                    top,
+                    output,
+                } => assert_eq!(Shape::stack(bottom, top), *output),
+                Step::Swap { east, west, output } => {
+                    assert_eq!(Shape::swap(east, west)[0], *output)
+                }
+            }
+        }
+        assert_eq!(plan.steps.last().unwrap().output(), plan.goal);
+    }
+
+    #[test]
+    fn test_plan_raw_and_painted() {
+        let plan_raw = Solver::new().solve(&shape("CuCuCuCu")).unwrap();
+        assert_eq!(
+            plan_raw.steps,
+            vec![Step::Extract {
+                output: shape("CuCuCuCu")
+            }]
+        );
+        let plan_painted = Solver::new().solve(&shape("RgRgRgRg")).unwrap();
+        assert_eq!(plan_painted.steps.len(), 2);
+        check_plan(&plan_painted);
+
+        // black isn't mixed from paint, so nothing paints it
+        assert!(matches!(
+            Solver::new().solve(&shape("CkCkCkCk")),
+            Err(SolveError::NotFound(_))
+        ));
+        assert!(matches!(
+            Solver::new().solve(&shape("CkCuCuCu")),
+            Err(SolveError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_plan_layers() {
+        for goal in ["CrRg----", "Cu--Cu--", "CrWbRgSy"] {
+            check_plan(&Solver::new().solve(&shape(goal)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_plan_multi_layer() {
+        for goal in [
+            "CuCuCuCu:RrRr----",
+            "P-P-----:CuCu----",
+            "CuCuCuCu:P-P-----:RuRu----",
+            "CuCuP-P-:----RuRu",
+        ] {
+            check_plan(&Solver::new().solve(&shape(goal)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_plan_impossible() {
+        let solver = Solver::new();
+        assert_eq!(
+            solver.solve(&shape("Cu------:--Cu----")),
+            Err(SolveError::Impossible(vec![Impossibility::Floating(vec![
+                (1, 1)
+            ])]))
+        );
+        assert_eq!(
+            solver
+                .solve(&shape("Cu------:--Cu----"))
+                .unwrap_err()
+                .to_string(),
+            "impossible to build\n- nothing holds up layer 1 part 1, it would fall down"
+        );
+        assert!(solver.solve(&shape("P-P-P-P-")).is_err());
+        assert!(solver.solve(&Shape::default()).is_err());
+
+        // crystals only come from start shapes that have them
+        assert_eq!(
+            solver.solve(&shape("cwcw----")),
+            Err(SolveError::Impossible(vec![
+                Impossibility::NoCrystalSource((0, 0)),
+                Impossibility::NoCrystalSource((0, 1)),
+            ]))
+        );
+        assert_eq!(
+            solver.solve(&shape("Cu------:cr------")),
+            Err(SolveError::Impossible(vec![
+                Impossibility::NoCrystalSource((1, 0))
+            ]))
+        );
+        let crystals = Solver::new().start_shapes(vec![layer("crcrcrcr")]);
+        assert!(crystals.solve(&shape("crcrcrcr")).is_ok());
+    }
+
+    #[test]
+    fn test_failure_in_cycle_not_kept() {
+        let solver = Solver::new().operations([OperationKind::Stack]);
+        let goal = shape("CuCuCuCu:SuSuSuSu");
+        let bottom = ShapeId::from(shape("CuCuCuCu"));
+        let mut known = Known::default();
+        let mut budget = solver.budget();
+        // the only split needs the bottom, which is pretended to be in progress
+        known.building.insert(bottom);
+        assert_eq!(solver.build(&goal, &mut known, &mut budget), Ok(None));
+        assert!(!known.plans.contains_key(&ShapeId::from(goal)));
+        assert_eq!(known.cycles, HashSet::from([bottom]));
+
+        known.building.clear();
+        known.cycles.clear();
+        let steps = solver.build(&goal, &mut known, &mut budget).unwrap();
+        assert_eq!(steps.unwrap().last().unwrap().output(), goal);
+    }
+
+    #[test]
+    fn test_operation_set() {
+        let no_paint = Solver::new().operations([
+            OperationKind::Rotate,
+            OperationKind::Cut,
+            OperationKind::Swap,
+        ]);
+        assert!(no_paint.solve(&shape("CrCrCrCr")).is_err());
+        assert!(no_paint.solve(&shape("CuCu----")).is_ok());
+
+        let rotate_only = Solver::new().operations([OperationKind::Rotate]);
+        let goal = SingleLayer::try_from_string("Cu------").unwrap();
+        let start = goal.rotate_180();
+        let solution = rotate_only
+            .start_shapes(vec![start])
+            .solve_layer(&goal)
+            .unwrap();
+        assert_eq!(
+            solution.operations,
+            vec![Operation::Rotate180 {
+                input: start,
+                output: goal
+            }]
+        );
+        assert_eq!(solution.path(), vec![start, goal]);
+    }
+
+    #[test]
+    fn test_limits() {
+        let goal = shape("CrWbRgSy");
+        assert!(matches!(
+            Solver::new().max_nodes(1).solve(&goal),
+            Err(SolveError::NodeLimit(SearchStats { expanded: 1, .. }))
+        ));
+        assert!(matches!(
+            Solver::new().time_limit(Duration::ZERO).solve(&goal),
+            Err(SolveError::TimeLimit(_))
+        ));
+
+        // the node arena runs out of indices before the counters wrap
+        let budget = Solver::new().budget();
+        assert_eq!(budget.node_index(7), Ok(7));
+        assert!(matches!(
+            budget.node_index(NO_PARENT as usize),
+            Err(SolveError::NodeLimit(_))
+        ));
+        assert!(matches!(
+            budget.successor_index(1 << 16),
+            Err(SolveError::NodeLimit(_))
+        ));
+    }
+
+    #[test]
+    fn test_recipe() {
+        let input = layer("CuCuCuCu");
+        let solution = Solver::new()
+            .start_shapes(vec![input])
+            .solve_layer(&layer("----CuCu"))
+            .unwrap();
+        assert_eq!(
+            solution.to_string(),
+            "start from CuCuCuCu\n\
+             1. cut CuCuCuCu and keep the west half -> ----CuCu"
+        );
+
+        let plan = Solver::new().solve(&shape("CrCrCrCr")).unwrap();
+        assert_eq!(
+            plan.to_string(),
+            "recipe for CrCrCrCr\n\
+             1. extract CuCuCuCu\n\
+             2. paint CuCuCuCu r -> CrCrCrCr"
+        );
+    }
+
+    /// Shortest number of operations from the start shapes to `goal`, by
+    /// plain breadth-first search
+    fn bfs(start_shapes: &[SingleLayer], goal: &SingleLayer) -> Option<usize> {
+        let mut seen: HashSet<SingleLayer> = start_shapes.iter().copied().collect();
+        let mut frontier = start_shapes.to_vec();
+        let mut depth = 0;
+        while !frontier.is_empty() {
+            if frontier.contains(goal) {
+                return Some(depth);
+            }
+            let mut next = Vec::new();
+            for layer in frontier {
+                for operation in Operation::successors(&layer, start_shapes) {
+                    if seen.insert(operation.output()) {
+                        next.push(operation.output());
+                    }
+                }
+            }
+            frontier = next;
+            depth += 1;
+        }
+        None
+    }
+
+    /// Start shapes and 100 random goals made from their parts
+    fn random_goals(seed: u64) -> (Vec<SingleLayer>, Vec<SingleLayer>) {
+        let start_shapes = vec![layer("CuCuCuCu"), layer("RrRrRrRr"), layer("SbWbSbWb")];
+        let items = [
+            SingleItem::default(),
+            start_shapes[0][0],
+            start_shapes[1][0],
+            start_shapes[2][0],
+            start_shapes[2][1],
+        ];
+        let mut rng = StdRng::seed_from_u64(seed);
+        let goals = (0..100)
+            .map(|_| {
+                let mut goal = SingleLayer::default();
+                for j in 0..SHAPEZ2_DEMENTION {
+                    goal.items[j] = items[rng.gen_range(0..items.len())];
+                }
+                goal
+            })
+            .collect();
+        (start_shapes, goals)
+    }
+
+    #[test]
+    fn test_shortest_paths() {
+        let (start_shapes, goals) = random_goals(13);
+        let solver = Solver::new().start_shapes(start_shapes.clone());
+        for goal in goals {
+            let found = solver.solve_layer(&goal).ok();
+            assert_eq!(
+                found.map(|solution| solution.operations.len()),
+                bfs(&start_shapes, &goal),
+                "goal {}",
+                goal
+            );
+        }
+    }
+
+    #[test]
+    fn test_bidirectional() {
+        let (start_shapes, goals) = random_goals(18);
+        let forward = Solver::new().start_shapes(start_shapes);
+        let bidirectional = forward.clone().bidirectional(true);
+        for goal in goals {
+            assert_eq!(
+                bidirectional
+                    .solve_layer(&goal)
+                    .ok()
+                    .map(|solution| solution.operations.len()),
+                forward
+                    .solve_layer(&goal)
+                    .ok()
+                    .map(|solution| solution.operations.len()),
+                "goal {}",
+                goal
+            );
+        }
+
+        for goal in ["CrWbRgSy", "CuCuP-P-:----RuRu", "Cu------:--Cu----"] {
+            let goal = shape(goal);
+            let forward = Solver::new().solve(&goal);
+            let bidirectional = Solver::new().bidirectional(true).solve(&goal);
+            assert_eq!(forward.is_ok(), bidirectional.is_ok());
+            if let Ok(plan) = bidirectional {
+                check_plan(&plan);
+            }
+        }
+    }
+
+    #[test]
+    fn test_heuristic() {
+        let goal = layer("CuRrCu--");
+        assert_eq!(heuristic(&goal, &goal), 0);
+        assert_eq!(heuristic(&layer("Cu--CuRr"), &goal), 1);
+        assert_eq!(heuristic(&layer("CuRrCuCu"), &goal), 1);
+        assert_eq!(heuristic(&layer("--------"), &goal), 2);
+        assert_eq!(heuristic(&layer("cbcbcbcb"), &goal), 1);
+    }
+
+    #[test]
+    fn test_cost_model() {
+        let start_shapes = vec![layer("CuCuCuCu"), layer("RrRrRrRr")];
+        let models = [
+            CostModel::new(Objective::FewestMachines),
+            CostModel::new(Objective::Weighted)
+                .weight(OperationKind::Rotate, 3)
+                .weight(OperationKind::Swap, 2),
+            CostModel::new(Objective::SmallestFootprint)
+                .area(OperationKind::Cut, 4)
+                .area(OperationKind::Swap, 6),
+            CostModel::new(Objective::FewestBuildingTypes),
+        ];
+        for goal in ["CuCuRrRr", "RrCuCuRr", "--RrRr--", "CuRrRr--"] {
+            let goal = layer(goal);
+            let solutions: Vec<LayerSolution> = models
+                .iter()
+                .map(|&model| {
+                    Solver::new()
+                        .start_shapes(start_shapes.clone())
+                        .cost_model(model)
+                        .solve_layer(&goal)
+                        .unwrap()
+                })
+                .collect();
+            // every model finds a path at least as cheap as the others' paths
+            for (model, solution) in models.iter().zip(solutions.iter()) {
+                assert_eq!(
+                    solution.cost,
+                    model.total(solution.operations.iter().map(Operation::kind))
+                );
+                for other in solutions.iter() {
+                    assert!(
+                        solution.cost <= model.total(other.operations.iter().map(Operation::kind))
+                    );
+                }
+            }
+        }
+
+        let plan = Solver::new()
+            .cost_model(CostModel::new(Objective::Weighted).weight(OperationKind::Paint, 5))
+            .solve(&shape("CrCrCrCr"))
+            .unwrap();
+        assert_eq!(plan.cost, 5);
+    }
+
+    #[test]
+    fn test_cheapest_split() {
+        // each layer needs a swap of its own, while the halves of the goal
+        // only need one to be put together
+        let goal = shape("Cu--Cu--:Ru--Ru--");
+        let solver = Solver::new()
+            .cost_model(CostModel::new(Objective::Weighted).weight(OperationKind::Swap, 5));
+        let plan = solver.solve(&goal).unwrap();
+        check_plan(&plan);
+        assert!(matches!(plan.steps.last(), Some(Step::Swap { .. })));
+
+        let mut known = Known::default();
+        let stacked = solver
+            .build_stack(&goal, &mut known, &mut solver.budget())
+            .unwrap()
+            .unwrap();
+        assert!(plan.cost < solver.steps_cost(&stacked));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_matches_sequential() {
+        for goal in [
+            "CrWbRgSy",
+            "CuCuCuCu:RrRr----",
+            "CuCuP-P-:----RuRu",
+            "CrCgCbCy:RuRuRuRu:SwSk----",
+            "Cu------:--Cu----",
+        ] {
+            let goal = shape(goal);
+            let sequential = Solver::new().threads(1).solve(&goal);
+            let parallel = Solver::new().threads(4).solve(&goal);
+            match (sequential, parallel) {
+                (Ok(sequential), Ok(parallel)) => {
+                    assert_eq!(sequential.steps, parallel.steps);
+                    assert_eq!(sequential.cost, parallel.cost);
+                    assert_eq!(sequential.stats.expanded, parallel.stats.expanded);
+                }
+                (Err(sequential), Err(parallel)) => {
+                    assert_eq!(sequential.stats().expanded, parallel.stats().expanded)
+                }
+                (sequential, parallel) => panic!("{:?} != {:?}", sequential, parallel),
+            }
+        }
+
+        let goal = shape("CrWbRgSy:CuCuCuCu");
+        for max_nodes in [1, 10, 100] {
+            let sequential = Solver::new().threads(1).max_nodes(max_nodes).solve(&goal);
+            let parallel = Solver::new().threads(4).max_nodes(max_nodes).solve(&goal);
+            let outcome = |result: Result<Plan, SolveError>| {
+                result
+                    .map(|plan| plan.steps)
+                    .map_err(|err| (std::mem::discriminant(&err), err.stats().expanded))
+            };
+            assert_eq!(outcome(sequential), outcome(parallel));
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_prefetch_shares_budget() {
+        let goal = shape("CrWbRgSy:SyRgWbCr:CgCgRbRb");
+        for max_nodes in [0, 5, 50] {
+            let solver = Solver::new().threads(4).max_nodes(max_nodes);
+            let mut known = Known::default();
+            solver.prefetch_layers(&goal, &mut known, &solver.budget());
+            let expanded: usize = known.layers.values().map(|(_, expanded)| expanded).sum();
+            assert!(expanded <= max_nodes, "{} > {}", expanded, max_nodes);
+        }
+    }
+
+    #[test]
+    fn test_search_cache() {
+        let start_shapes = vec![layer("CuCuCuCu"), layer("RrRrRrRr")];
+        let cache = Arc::new(SearchCache::build(start_shapes.clone()));
+        let solver = Solver::new()
+            .start_shapes(start_shapes.clone())
+            .search_cache(cache.clone());
+        for goal in ["CuCuRrRr", "Cu--RrCu", "--Rr----"] {
+            let goal = layer(goal);
+            let found = solver.solve_layer(&goal).unwrap();
+            assert_eq!(found.stats.expanded, 0);
+            assert_eq!(found.operations.len(), bfs(&start_shapes, &goal).unwrap());
+        }
+        assert!(matches!(
+            solver.solve_layer(&layer("Sb------")),
+            Err(SolveError::NotFound(SearchStats { expanded: 0, .. }))
+        ));
+
+        // other objectives than the fewest operations search as usual
+        let weighted = solver
+            .clone()
+            .cost_model(CostModel::new(Objective::Weighted).weight(OperationKind::Swap, 5));
+        let found = weighted.solve_layer(&layer("CuCuRrRr")).unwrap();
+        assert!(found.stats.expanded > 0);
+
+        // recipes swapping with shapes the search doesn't have are searched
+        let only_circles = Solver::new()
+            .start_shapes(vec![layer("CuCuCuCu")])
+            .search_cache(cache);
+        let found = only_circles.solve_layer(&layer("Cu------")).unwrap();
+        assert_eq!(
+            found.operations.len(),
+            bfs(&[layer("CuCuCuCu")], &layer("Cu------")).unwrap()
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use crate::shape::{Shape, SHAPEZ2_LAYER};
+use crate::{
+    physics,
+    shape::{GenericLayer, GenericShape},
+};
 
 pub trait Stackable: Sized + Copy {
     fn stacked_with(&mut self, other_on_top: &Self);
@@ -17,35 +20,96 @@ pub trait Stackable: Sized + Copy {
     }
 }
 
-impl Stackable for Shape {
+/// The top shape is dropped onto the bottom one: its layers are placed right
+/// above the bottom shape, every unsupported part falls until it rests on
+/// something, and whatever ends up above the layer limit is discarded.
+impl<const N: usize, const L: usize> Stackable for GenericShape<N, L> {
     fn stacked_with(&mut self, other_on_top: &Self) {
-        let ori_layer_height = self.layer_height();
-        let layer_needed = SHAPEZ2_LAYER - ori_layer_height;
-        for i in 0..layer_needed {
-            self[ori_layer_height + i] = other_on_top[i];
-        }
+        let mut layers: Vec<GenericLayer<N>> = self.items[..self.layer_height()].to_vec();
+        layers.extend_from_slice(&other_on_top.items[..other_on_top.layer_height()]);
+        *self = physics::settle(layers);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::shape::SHAPEZ2_DEMENTION;
-
     use super::*;
+    use crate::shape::{shape, HexShape, Shape, SHAPEZ2_DEMENTION, SHAPEZ2_LAYER};
     use pretty_assertions::assert_eq;
 
     #[test]
     fn test_stack() {
-        let layer_height = 2;
-        let bot = Shape::random_with_height(layer_height);
-        let top = Shape::random_with_height(SHAPEZ2_DEMENTION - layer_height);
+        let bot = shape("CuCuCuCu:RrRrRrRr");
+        let top = shape("SgSgSgSg:WbWbWbWb");
         let new_shape = Shape::stack(&bot, &top);
         for i in 0..SHAPEZ2_LAYER {
-            if i < layer_height {
+            if i < 2 {
                 assert_eq!(new_shape.items[i], bot.items[i]);
             } else {
-                assert_eq!(new_shape.items[i], top.items[i - layer_height]);
+                assert_eq!(new_shape.items[i], top.items[i - 2]);
             }
         }
     }
+
+    #[test]
+    fn test_stack_falls_through() {
+        let bot = shape("Cu------");
+        assert_eq!(Shape::stack(&bot, &shape("------Cu")), shape("Cu----Cu"));
+        assert_eq!(
+            Shape::stack(&bot, &shape("--Cu----:--Cu----")),
+            shape("CuCu----:--Cu----")
+        );
+    }
+
+    #[test]
+    fn test_stack_connected_parts_hold() {
+        let bot = shape("Cu------");
+        assert_eq!(
+            Shape::stack(&bot, &shape("CuCu----")),
+            shape("Cu------:CuCu----")
+        );
+    }
+
+    #[test]
+    fn test_stack_pins_do_not_connect() {
+        let bot = shape("Cu------");
+        assert_eq!(
+            Shape::stack(&bot, &shape("P-P-----")),
+            shape("CuP-----:P-------")
+        );
+    }
+
+    #[test]
+    fn test_stack_overflow_discarded() {
+        let bot = shape("CuCuCuCu:CuCuCuCu:CuCuCuCu");
+        let top = shape("RuRuRuRu:SuSuSuSu");
+        assert_eq!(
+            Shape::stack(&bot, &top),
+            shape("CuCuCuCu:CuCuCuCu:CuCuCuCu:RuRuRuRu")
+        );
+
+        let full = shape("CuCuCuCu:CuCuCuCu:CuCuCuCu:CuCuCuCu");
+        assert_eq!(Shape::stack(&full, &top), full);
+    }
+
+    #[test]
+    fn test_stack_hex() {
+        let bot = HexShape::try_from_string("CuCuCu------").unwrap();
+        let top = HexShape::try_from_string("----RuRuRu--").unwrap();
+        assert_eq!(
+            HexShape::stack(&bot, &top),
+            HexShape::try_from_string("CuCuCu------:----RuRuRu--").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_stack_layer_limit() {
+        type InsaneShape = GenericShape<SHAPEZ2_DEMENTION, 5>;
+        let bot = InsaneShape::try_from_string("CuCuCuCu:CuCuCuCu:CuCuCuCu").unwrap();
+        let top = InsaneShape::try_from_string("RuRuRuRu:SuSuSuSu:WuWuWuWu").unwrap();
+        assert_eq!(
+            InsaneShape::stack(&bot, &top),
+            InsaneShape::try_from_string("CuCuCuCu:CuCuCuCu:CuCuCuCu:RuRuRuRu:SuSuSuSu").unwrap()
+        );
+    }
 }
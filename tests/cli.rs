@@ -0,0 +1,172 @@
+use pretty_assertions::assert_eq;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Run the command line tool with `stdin` as standard input, returning
+/// whether it succeeded, its output and its errors
+fn run(args: &[&str], stdin: &str) -> (bool, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_shapez2-calc"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_shape_commands() {
+    assert_eq!(
+        run(&["rotate", "cw", "CuRu----", "Cu"], ""),
+        (true, "--CuRu\n--Cu\n".to_string(), String::new())
+    );
+    assert_eq!(
+        run(&["cut", "CuRuSuWu"], ""),
+        (true, "----SuWu CuRu\n".to_string(), String::new())
+    );
+    assert_eq!(
+        run(&["stack", "CuCuCuCu", "Ru------"], ""),
+        (true, "CuCuCuCu:Ru------\n".to_string(), String::new())
+    );
+    assert_eq!(
+        run(&["normalize", "Cu------:--------"], ""),
+        (true, "Cu\n".to_string(), String::new())
+    );
+
+    let (ok, _, errors) = run(&["rotate", "sideways", "Cu"], "");
+    assert!(!ok);
+    assert!(errors.contains("unknown direction"), "{}", errors);
+    let (ok, _, errors) = run(&["swap", "Cu"], "");
+    assert!(!ok);
+    assert_eq!(errors, "Cu: swap takes 2 shapes, got 1\n");
+}
+
+#[test]
+fn test_stdin() {
+    // one operation per line, blank lines skipped, bad lines reported
+    let (ok, output, errors) = run(&["swap"], "CuCuCuCu RuRuRuRu\n\nCu\nCuCu Ru:Xx\n");
+    assert!(!ok);
+    assert_eq!(output, "CuCuRuRu RuRuCuCu\n");
+    assert_eq!(errors.lines().count(), 2);
+    assert!(errors.starts_with("Cu: swap takes 2 shapes, got 1\n"));
+
+    let (ok, output, _) = run(&["rotate", "180"], "Cu\nRu Su\n");
+    assert!(ok);
+    assert_eq!(output, "----Cu\n----Ru\n----Su\n");
+}
+
+#[test]
+fn test_solve_options() {
+    let (ok, output, _) = run(&["solve", "RgRgRgRg"], "");
+    assert!(ok);
+    assert!(output.starts_with("recipe for RgRgRgRg\n"), "{}", output);
+    for options in [
+        &["--max-nodes", "100000"][..],
+        &["--time-limit", "30"],
+        &["--objective", "fewest-building-types"],
+        &["--bidirectional"],
+        &[
+            "--bidirectional",
+            "--objective",
+            "weighted",
+            "--max-nodes",
+            "1000",
+        ],
+    ] {
+        let args: Vec<&str> = ["solve"]
+            .iter()
+            .chain(options)
+            .chain(&["CuCuRuRu"])
+            .copied()
+            .collect();
+        let (ok, output, errors) = run(&args, "");
+        assert!(ok, "{:?}: {}", options, errors);
+        assert!(output.starts_with("recipe for CuCuRuRu\n"), "{}", output);
+    }
+
+    let (ok, _, errors) = run(&["solve", "--max-nodes", "1", "CrWbRgSy"], "");
+    assert!(!ok);
+    assert!(errors.contains("node limit reached"), "{}", errors);
+
+    for (args, error) in [
+        (
+            &["solve", "--max-nodes", "lots", "Cu"][..],
+            "invalid value \"lots\" for --max-nodes",
+        ),
+        (
+            &["solve", "--time-limit", "-1", "Cu"],
+            "invalid value \"-1\" for --time-limit",
+        ),
+        (
+            &["solve", "--objective", "cheapest", "Cu"],
+            "unknown objective \"cheapest\"",
+        ),
+        (
+            &["solve", "--frobnicate", "Cu"],
+            "unknown option \"--frobnicate\"",
+        ),
+        (&["solve", "--cache"], "--cache needs a value"),
+        (
+            &["solve", "--cache", "/nonexistent/table", "Cu"],
+            "/nonexistent/table: ",
+        ),
+    ] {
+        let (ok, _, errors) = run(args, "");
+        assert!(!ok);
+        assert!(errors.starts_with(error), "{:?}: {}", args, errors);
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_solve_threads() {
+    let sequential = run(&["solve", "--threads", "1", "CrWbRgSy:CuCuCuCu"], "");
+    let parallel = run(&["solve", "--threads", "4", "CrWbRgSy:CuCuCuCu"], "");
+    assert!(sequential.0);
+    assert_eq!(sequential, parallel);
+}
+
+#[test]
+fn test_random_shapes_validate() {
+    let (ok, shapes, _) = run(&["random", "200"], "");
+    assert!(ok);
+    assert_eq!(shapes.lines().count(), 200);
+    let (ok, output, errors) = run(&["validate"], &shapes);
+    assert!(ok, "{}", errors);
+    assert_eq!(output.lines().count(), 200);
+
+    let (ok, _, errors) = run(&["random", "many"], "");
+    assert!(!ok);
+    assert_eq!(errors, "invalid count \"many\"\n");
+}
+
+#[test]
+fn test_eval_stdin() {
+    let (ok, output, errors) = run(&["eval"], "x = CuCuCuCu\nrot_cw(x)\ny = nope(x)\ncut(x)\n");
+    assert!(!ok);
+    assert_eq!(output, "CuCuCuCu\n----CuCu CuCu\n");
+    assert!(errors.starts_with("line 3: "), "{}", errors);
+}
+
+#[test]
+fn test_repl() {
+    let (ok, output, errors) = run(&["repl"], "x = CuRu----\n:undo\n:frobnicate\n:quit\n");
+    assert!(ok);
+    assert!(output.contains("x = CuRu----\n"), "{}", output);
+    assert!(output.contains("undid x = CuRu----\n"), "{}", output);
+    assert!(errors.contains("unknown command"), "{}", errors);
+}